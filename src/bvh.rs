@@ -0,0 +1,562 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    interval::Interval,
+    light::Light,
+    ray::Ray,
+    vec3::Point3,
+};
+
+// Bounding volume hierarchy node. Each node owns its children, which are either further
+// BvhNodes or the scene objects themselves at the leaves.
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Option<Box<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        Self::from_objects(list.objects)
+    }
+
+    fn from_objects(mut objects: Vec<Box<dyn Hittable>>) -> Self {
+        // Build the bounding box of the span of source objects
+        let bbox = objects.iter().fold(Aabb::EMPTY, |bbox, object| {
+            Aabb::surrounding(&bbox, &object.bounding_box())
+        });
+
+        let axis = bbox.longest_axis();
+
+        let (left, right): (Box<dyn Hittable>, Option<Box<dyn Hittable>>) = match objects.len() {
+            0 => (Box::new(HittableList::new()), None),
+            1 => (objects.pop().unwrap(), None),
+            2 => {
+                let b = objects.pop().unwrap();
+                let a = objects.pop().unwrap();
+                (a, Some(b))
+            }
+            _ => {
+                objects.sort_by(|a, b| box_compare(a.as_ref(), b.as_ref(), axis));
+
+                let mid = objects.len() / 2;
+                let upper = objects.split_off(mid);
+                (
+                    Box::new(Self::from_objects(objects)),
+                    Some(Box::new(Self::from_objects(upper))),
+                )
+            }
+        };
+
+        BvhNode { left, right, bbox }
+    }
+}
+
+// Smallest k with 2^k >= n
+fn ceil_log2(n: usize) -> usize {
+    n.next_power_of_two().trailing_zeros() as usize
+}
+
+impl BvhNode {
+    // Closest of the hits the query finds on the children
+    fn closest_hit<'a, F>(&'a self, r: &Ray, ray_t: Interval, query: F) -> Option<HitRecord<'a>>
+    where
+        F: Fn(&'a dyn Hittable, &Ray, Interval) -> Option<HitRecord<'a>>,
+    {
+        if !self.bbox.hit(r, ray_t) {
+            return None;
+        }
+
+        let hit_left = query(self.left.as_ref(), r, ray_t);
+
+        // Only look for hits in the right subtree that are closer than the left hit
+        let right_max = hit_left.as_ref().map_or(ray_t.max, |rec| rec.t);
+        let hit_right = self
+            .right
+            .as_ref()
+            .and_then(|right| query(right.as_ref(), r, Interval::new(ray_t.min, right_max)));
+
+        hit_right.or(hit_left)
+    }
+}
+
+fn box_compare(a: &dyn Hittable, b: &dyn Hittable, axis_index: usize) -> Ordering {
+    let a_axis_interval = a.bounding_box().axis_interval(axis_index).min;
+    let b_axis_interval = b.bounding_box().axis_interval(axis_index).min;
+    a_axis_interval.total_cmp(&b_axis_interval)
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.closest_hit(r, ray_t, |object, r, ray_t| object.hit(r, ray_t))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.closest_hit(r, ray_t, |object, r, ray_t| object.shadow_hit(r, ray_t))
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        if !self.bbox.hit(r, ray_t) {
+            return 1.0;
+        }
+
+        self.left.transmittance(r, ray_t)
+            * self
+                .right
+                .as_ref()
+                .map_or(1.0, |right| right.transmittance(r, ray_t))
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Light>) {
+        self.left.collect_lights(lights);
+        if let Some(right) = &self.right {
+            right.collect_lights(lights);
+        }
+    }
+}
+
+const SAH_BIN_COUNT: usize = 12;
+const MAX_LEAF_PRIMITIVES: usize = 4;
+const TRAVERSAL_COST: f64 = 0.125; // relative to the cost of one primitive intersection
+const MAX_TRAVERSAL_DEPTH: usize = 64;
+
+// Node of a flattened BVH. The left child of an interior node is always stored right after
+// its parent, so only the index of the right child needs to be kept.
+#[derive(Debug, Clone, Copy)]
+struct LinearBvhNode {
+    bbox: Aabb,
+    offset: usize, // first primitive index for leaves, right child index for interior nodes
+    count: usize,  // number of primitives for leaves, zero for interior nodes
+    axis: usize,   // split axis for interior nodes
+}
+
+// Summary of a built BVH, for comparing build quality against the flat HittableList path
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BvhStats {
+    pub primitive_count: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub max_leaf_size: usize,
+    pub sah_cost: f64, // expected cost of a ray query, in units of primitive intersections
+}
+
+impl BvhStats {
+    // Expected cost of testing every primitive, as HittableList does
+    pub fn list_cost(&self) -> f64 {
+        self.primitive_count as f64
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "BVH: {} primitives, {} nodes ({} leaves, max {} primitives per leaf), depth {}, \
+             SAH cost {:.2} (flat list cost {:.2})",
+            self.primitive_count,
+            self.node_count,
+            self.leaf_count,
+            self.max_leaf_size,
+            self.max_depth,
+            self.sah_cost,
+            self.list_cost(),
+        )
+    }
+}
+
+// Flattened BVH built with binned surface area heuristic splits. The tree only knows about
+// primitive bounding boxes; callers own the primitives and intersect them by index.
+pub struct BvhTree {
+    nodes: Vec<LinearBvhNode>,
+    indices: Vec<usize>,
+    stats: BvhStats,
+}
+
+impl BvhTree {
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let centroids: Vec<Point3> = bounds.iter().map(Aabb::centroid).collect();
+        let mut tree = BvhTree {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+            stats: BvhStats::default(),
+        };
+
+        if !bounds.is_empty() {
+            tree.build_recursive(bounds, &centroids, 0, bounds.len(), 1);
+        }
+        tree.compute_stats();
+        tree
+    }
+
+    fn build_recursive(
+        &mut self,
+        bounds: &[Aabb],
+        centroids: &[Point3],
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> usize {
+        let node_index = self.nodes.len();
+        let count = end - start;
+
+        let bbox = self.indices[start..end]
+            .iter()
+            .fold(Aabb::EMPTY, |bbox, &i| Aabb::surrounding(&bbox, &bounds[i]));
+
+        let leaf = LinearBvhNode {
+            bbox,
+            offset: start,
+            count,
+            axis: 0,
+        };
+        self.nodes.push(leaf);
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        if count == 1 {
+            return node_index;
+        }
+
+        // Choose the split axis from the spread of primitive centroids, not their full bounds
+        let centroid_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::EMPTY, |bbox, &i| {
+                Aabb::surrounding(&bbox, &Aabb::from_points(centroids[i], centroids[i]))
+            });
+        let axis = centroid_bounds.longest_axis();
+        let extent = *centroid_bounds.axis_interval(axis);
+
+        if extent.size() <= 0.0 {
+            // All centroids coincide, so no split can separate the primitives
+            return node_index;
+        }
+
+        // Median splits halve the primitive count at every level. Switch to them once further
+        // SAH splits, which may peel off a single primitive each, could make the tree deeper
+        // than the traversal stack.
+        let mut mid = if depth + ceil_log2(count) >= MAX_TRAVERSAL_DEPTH {
+            start
+        } else {
+            match self.sah_partition(bounds, centroids, start, end, axis, extent) {
+                Some(mid) => mid,
+                None => return node_index,
+            }
+        };
+
+        if mid == start || mid == end {
+            // Degenerate binning or too deep, fall back to splitting at the centroid median
+            mid = start + count / 2;
+            self.indices[start..end]
+                .sort_by(|&a, &b| centroids[a][axis].total_cmp(&centroids[b][axis]));
+        }
+
+        self.build_recursive(bounds, centroids, start, mid, depth + 1);
+        let right = self.build_recursive(bounds, centroids, mid, end, depth + 1);
+
+        self.nodes[node_index] = LinearBvhNode {
+            bbox,
+            offset: right,
+            count: 0,
+            axis,
+        };
+        node_index
+    }
+
+    // Partition the primitives around the binned SAH split with the lowest cost, returning the
+    // index that divides the two sides, or None if a leaf is cheaper
+    fn sah_partition(
+        &mut self,
+        bounds: &[Aabb],
+        centroids: &[Point3],
+        start: usize,
+        end: usize,
+        axis: usize,
+        extent: Interval,
+    ) -> Option<usize> {
+        let count = end - start;
+
+        let bin_of = |i: usize| -> usize {
+            let b =
+                (SAH_BIN_COUNT as f64 * (centroids[i][axis] - extent.min) / extent.size()) as usize;
+            b.min(SAH_BIN_COUNT - 1)
+        };
+
+        let mut bin_bounds = [Aabb::EMPTY; SAH_BIN_COUNT];
+        let mut bin_counts = [0usize; SAH_BIN_COUNT];
+        for &i in &self.indices[start..end] {
+            let b = bin_of(i);
+            bin_bounds[b] = Aabb::surrounding(&bin_bounds[b], &bounds[i]);
+            bin_counts[b] += 1;
+        }
+
+        // Sweep from both ends to get the cost of splitting after each bin
+        let mut costs = [0.0; SAH_BIN_COUNT - 1];
+        let mut below_bounds = Aabb::EMPTY;
+        let mut below_count = 0;
+        for b in 0..SAH_BIN_COUNT - 1 {
+            below_bounds = Aabb::surrounding(&below_bounds, &bin_bounds[b]);
+            below_count += bin_counts[b];
+            costs[b] = below_count as f64 * below_bounds.surface_area();
+        }
+        let mut above_bounds = Aabb::EMPTY;
+        let mut above_count = 0;
+        for b in (1..SAH_BIN_COUNT).rev() {
+            above_bounds = Aabb::surrounding(&above_bounds, &bin_bounds[b]);
+            above_count += bin_counts[b];
+            costs[b - 1] += above_count as f64 * above_bounds.surface_area();
+        }
+
+        let (best_bin, best_cost) =
+            costs
+                .iter()
+                .enumerate()
+                .fold((0, f64::INFINITY), |best, (b, &cost)| {
+                    if cost < best.1 {
+                        (b, cost)
+                    } else {
+                        best
+                    }
+                });
+
+        // The last sweep plus the first bin covers every primitive
+        let area = Aabb::surrounding(&above_bounds, &bin_bounds[0]).surface_area();
+        let split_cost = if area > 0.0 {
+            TRAVERSAL_COST + best_cost / area
+        } else {
+            TRAVERSAL_COST
+        };
+        let leaf_cost = count as f64;
+
+        if count <= MAX_LEAF_PRIMITIVES && leaf_cost <= split_cost {
+            return None;
+        }
+
+        // Partition the primitive indices around the chosen bin
+        let mut mid = start;
+        for k in start..end {
+            if bin_of(self.indices[k]) <= best_bin {
+                self.indices.swap(k, mid);
+                mid += 1;
+            }
+        }
+
+        Some(mid)
+    }
+
+    fn compute_stats(&mut self) {
+        self.stats.primitive_count = self.indices.len();
+        self.stats.node_count = self.nodes.len();
+
+        let root_area = self.bounding_box().surface_area();
+        for node in &self.nodes {
+            let area_ratio = if root_area > 0.0 {
+                node.bbox.surface_area() / root_area
+            } else {
+                1.0
+            };
+
+            if node.count > 0 {
+                self.stats.leaf_count += 1;
+                self.stats.max_leaf_size = self.stats.max_leaf_size.max(node.count);
+                self.stats.sah_cost += area_ratio * node.count as f64;
+            } else {
+                self.stats.sah_cost += area_ratio * TRAVERSAL_COST;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |root| root.bbox)
+    }
+
+    // Find the closest hit along the ray. The callback intersects the primitive with the given
+    // index (as passed to BvhTree::new) against the ray over the given interval.
+    pub fn hit<'a, F>(
+        &self,
+        r: &Ray,
+        ray_t: Interval,
+        mut hit_primitive: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, &Ray, Interval) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let dir_is_neg = [
+            r.direction().x() < 0.0,
+            r.direction().y() < 0.0,
+            r.direction().z() < 0.0,
+        ];
+
+        let mut closest_hit: Option<HitRecord<'a>> = None;
+        let mut closest_so_far = ray_t.max;
+
+        let mut stack = [0usize; MAX_TRAVERSAL_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bbox.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                if node.count > 0 {
+                    for &i in &self.indices[node.offset..node.offset + node.count] {
+                        if let Some(rec) =
+                            hit_primitive(i, r, Interval::new(ray_t.min, closest_so_far))
+                        {
+                            closest_so_far = rec.t;
+                            closest_hit = Some(rec);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first, defer the other one
+                    let (near, far) = if dir_is_neg[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        closest_hit
+    }
+}
+
+// Hittable wrapper that owns a set of scene objects and a flattened BVH over them. Unbounded
+// objects such as infinite planes are kept out of the tree and tested on every ray.
+pub struct Bvh {
+    objects: Vec<Box<dyn Hittable>>,
+    unbounded: Vec<Box<dyn Hittable>>,
+    tree: BvhTree,
+}
+
+impl Bvh {
+    pub fn new(list: HittableList) -> Self {
+        let (objects, unbounded): (Vec<_>, Vec<_>) = list
+            .objects
+            .into_iter()
+            .partition(|o| o.bounding_box().is_bounded());
+
+        let bounds: Vec<Aabb> = objects.iter().map(|o| o.bounding_box()).collect();
+        Bvh {
+            objects,
+            unbounded,
+            tree: BvhTree::new(&bounds),
+        }
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        self.tree.stats()
+    }
+
+    // Closest of the hits the query finds on the objects
+    fn closest_hit<'a, F>(&'a self, r: &Ray, ray_t: Interval, query: F) -> Option<HitRecord<'a>>
+    where
+        F: Fn(&'a dyn Hittable, &Ray, Interval) -> Option<HitRecord<'a>>,
+    {
+        let mut closest_hit = self.tree.hit(r, ray_t, |i, r, ray_t| {
+            query(self.objects[i].as_ref(), r, ray_t)
+        });
+
+        for object in &self.unbounded {
+            let closest_so_far = closest_hit.as_ref().map_or(ray_t.max, |rec| rec.t);
+            if let Some(rec) = query(object.as_ref(), r, Interval::new(ray_t.min, closest_so_far)) {
+                closest_hit = Some(rec);
+            }
+        }
+
+        closest_hit
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.closest_hit(r, ray_t, |object, r, ray_t| object.hit(r, ray_t))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.unbounded
+            .iter()
+            .fold(self.tree.bounding_box(), |bbox, object| {
+                Aabb::surrounding(&bbox, &object.bounding_box())
+            })
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.closest_hit(r, ray_t, |object, r, ray_t| object.shadow_hit(r, ray_t))
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        // The callback never reports a hit, so the tree visits every object along the ray
+        let mut transmittance = 1.0;
+        self.tree.hit(r, ray_t, |i, r, ray_t| {
+            transmittance *= self.objects[i].transmittance(r, ray_t);
+            None
+        });
+
+        self.unbounded
+            .iter()
+            .fold(transmittance, |transmittance, object| {
+                transmittance * object.transmittance(r, ray_t)
+            })
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Light>) {
+        for object in self.objects.iter().chain(&self.unbounded) {
+            object.collect_lights(lights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn skewed_primitives_stay_within_traversal_depth() {
+        // Exponentially spaced boxes leave all but the last centroid in the first SAH bin, so
+        // every binned split peels off a single primitive
+        let bounds: Vec<Aabb> = (0..150)
+            .map(|i| {
+                let x = 16.0_f64.powi(i);
+                Aabb::from_points(Point3::new(x, -1.0, -1.0), Point3::new(1.1 * x, 1.0, 1.0))
+            })
+            .collect();
+        let tree = BvhTree::new(&bounds);
+        assert!(tree.stats().max_depth <= MAX_TRAVERSAL_DEPTH);
+
+        // A ray along the row passes through every box
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut visited = vec![false; bounds.len()];
+        tree.hit(&r, Interval::new(0.0, f64::INFINITY), |i, _, _| {
+            visited[i] = true;
+            None
+        });
+        assert!(visited.iter().all(|&v| v));
+    }
+}
@@ -7,19 +7,21 @@ use rand::Rng;
 
 use crate::{
     color::{write_color, Color},
-    hittable::Hittable,
-    hittable_list::HittableList,
+    hittable::{HitRecord, Hittable},
     interval::Interval,
+    light::LightList,
+    material::Lobe,
     ray::Ray,
     utils,
     vec3::{Point3, Vec3},
 };
 
 pub struct Camera {
-    pub aspect_ratio: f64,      // ratio of image width over height
-    pub image_width: i32,       // rendered image width in pixel count
-    pub samples_per_pixel: i32, // count of random samples for each pixel
-    pub max_depth: i32,         // maximum number of ray bounces into scene
+    pub aspect_ratio: f64,         // ratio of image width over height
+    pub image_width: i32,          // rendered image width in pixel count
+    pub samples_per_pixel: i32,    // count of random samples for each pixel
+    pub max_depth: i32,            // maximum number of ray bounces into scene
+    pub background: Option<Color>, // color of rays that escape the scene, or None for a sky
 
     pub vfov: f64,        // vertical view angle (field of view)
     pub lookfrom: Point3, // point camera is looking from
@@ -29,6 +31,9 @@ pub struct Camera {
     pub defocus_angle: f64, // variation angle of rays through each pixel
     pub focus_dist: f64,    // distance from camera lookfrom point to plane of perfect focus
 
+    pub shutter_open: f64,  // time the shutter opens
+    pub shutter_close: f64, // time the shutter closes, rays are sampled uniformly in between
+
     image_height: i32,        // rendered image height
     pixel_samples_scale: f64, // color scale factor for a sum of pixel samples
     center: Point3,           // camera center
@@ -42,6 +47,12 @@ pub struct Camera {
     defocus_disk_v: Vec3, // defocus disk vertical radius
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Camera {
     pub fn new() -> Self {
         Camera {
@@ -49,12 +60,15 @@ impl Camera {
             image_width: 100,
             samples_per_pixel: 10,
             max_depth: 10,
+            background: None,
             vfov: 90.0,
             lookfrom: Point3::new(0.0, 0.0, 0.0),
             lookat: Point3::new(0.0, 0.0, -1.0),
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
             image_height: 0,
             pixel_samples_scale: 0.0,
             center: Point3::default(),
@@ -106,17 +120,54 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
     }
 
-    fn ray_color(&self, r: &Ray, depth: i32, world: &HittableList) -> Color {
+    // Radiance arriving along the ray. scattering_pdf is the density with which the ray was
+    // sampled from a smooth lobe while the lights were sampled too, or None for camera rays and
+    // specular bounces.
+    fn ray_color(
+        &self,
+        r: &Ray,
+        depth: i32,
+        world: &dyn Hittable,
+        lights: &LightList,
+        scattering_pdf: Option<f64>,
+    ) -> Color {
         if depth <= 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
 
         if let Some(hit_rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) {
-            if let Some((attenuation, scattered)) = hit_rec.mat.scatter(r, &hit_rec) {
-                return attenuation * self.ray_color(&scattered, depth - 1, world);
-            }
+            // Light found by scattering could also have been found by sampling the lights at the
+            // previous bounce, so only count its share
+            let emission_weight = scattering_pdf.map_or(1.0, |scattering_pdf| {
+                power_heuristic(
+                    scattering_pdf,
+                    lights.pdf_value(&r.origin(), &r.direction(), r.time()),
+                )
+            });
+            let color_from_emission = emission_weight * hit_rec.mat.emitted(&hit_rec);
+
+            let Some(sample) = hit_rec.mat.sample(r, &hit_rec) else {
+                return color_from_emission;
+            };
+
+            // Only smooth lobes can be matched against the lights. On the last bounce the
+            // scattered ray can't reach a light, so the lights aren't sampled either.
+            let (scattering_pdf, color_from_lights) = match sample.lobe {
+                Lobe::Smooth { pdf } if depth > 1 && !lights.is_empty() => {
+                    let color = self.sample_lights(r, &hit_rec, world, lights);
+                    (Some(pdf), color)
+                }
+                _ => (None, Color::new(0.0, 0.0, 0.0)),
+            };
 
-            return Color::new(0.0, 0.0, 0.0);
+            let scattered = Ray::with_time(hit_rec.p, sample.direction, r.time());
+            let color_from_scatter = sample.weight
+                * self.ray_color(&scattered, depth - 1, world, lights, scattering_pdf);
+            return color_from_emission + color_from_lights + color_from_scatter;
+        }
+
+        if let Some(background) = self.background {
+            return background;
         }
 
         let unit_direction = r.direction().unit_vector();
@@ -124,9 +175,44 @@ impl Camera {
         (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
     }
 
+    // Next event estimation: pick a direction toward one of the lights, trace a shadow ray
+    // along it, dimmed by the participating media it passes through, and weight whatever light
+    // it reaches against the chance that scattering would have found it instead
+    fn sample_lights(
+        &self,
+        r: &Ray,
+        rec: &HitRecord,
+        world: &dyn Hittable,
+        lights: &LightList,
+    ) -> Color {
+        let direction = lights.random(&rec.p, r.time());
+        let light_pdf = lights.pdf_value(&rec.p, &direction, r.time());
+        if light_pdf <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let scattering_pdf = rec.mat.pdf(r, rec, &direction);
+        if scattering_pdf <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let shadow_ray = Ray::with_time(rec.p, direction, r.time());
+        let Some(light_rec) = world.shadow_hit(&shadow_ray, Interval::new(0.001, f64::INFINITY))
+        else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+        let transmittance = world.transmittance(&shadow_ray, Interval::new(0.001, light_rec.t));
+
+        let weight = power_heuristic(light_pdf, scattering_pdf);
+        (weight * transmittance / light_pdf)
+            * rec.mat.eval(r, rec, &direction)
+            * light_rec.mat.emitted(&light_rec)
+    }
+
     fn get_ray(&self, i: i32, j: i32) -> Ray {
         // Construct a camera ray originating from the defocus disk and directed at a randomly
-        // sampled point around the pixel location i, j.
+        // sampled point around the pixel location i, j, at a random time while the shutter is
+        // open.
 
         let offset = self.sample_square();
         let pixel_sample = self.pixel00_loc
@@ -139,8 +225,11 @@ impl Camera {
             self.defocus_disk_sample()
         };
         let ray_direction = pixel_sample - ray_origin;
+        // Interpolating instead of sampling a range also works for an instant or reversed shutter
+        let ray_time = self.shutter_open
+            + rand::thread_rng().gen::<f64>() * (self.shutter_close - self.shutter_open);
 
-        Ray::new(ray_origin, ray_direction)
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
     fn sample_square(&self) -> Vec3 {
@@ -153,8 +242,12 @@ impl Camera {
         self.center + (p[0] * self.defocus_disk_u) + (p[1] * self.defocus_disk_v)
     }
 
-    pub fn render(&mut self, world: &HittableList) -> io::Result<()> {
+    // Lights found in the world are sampled with next event estimation: at every bounce off a
+    // smooth lobe a shadow ray is also cast toward a point on one of them, and combined with the
+    // scattered ray by multiple importance sampling
+    pub fn render(&mut self, world: &dyn Hittable) -> io::Result<()> {
         self.initialize();
+        let lights = LightList::new(world);
 
         let file = File::create("image.ppm")?;
         let mut out = BufWriter::new(file);
@@ -171,7 +264,7 @@ impl Camera {
                 let mut pixel_color = Color::new(0.0, 0.0, 0.0);
                 for _sample in 0..self.samples_per_pixel {
                     let r = self.get_ray(i, j);
-                    pixel_color += self.ray_color(&r, self.max_depth, world);
+                    pixel_color += self.ray_color(&r, self.max_depth, world, &lights, None);
                 }
                 write_color(&mut out, &(self.pixel_samples_scale * pixel_color))?;
             }
@@ -181,3 +274,78 @@ impl Camera {
         Ok(())
     }
 }
+
+// Multiple importance sampling weight of a sample drawn with density pdf, against another
+// strategy that could have drawn it with density other_pdf
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable_list::HittableList, material::Material, quad::Quad, sphere::Sphere};
+
+    // Grey floor lit only by a small, moving emissive sphere
+    fn scene() -> HittableList {
+        let mut world = HittableList::new();
+        world.add(Box::new(Quad::new(
+            Point3::new(-50.0, 0.0, -50.0),
+            Vec3::new(100.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 100.0),
+            Material::lambertian(Color::new(0.5, 0.5, 0.5)),
+        )));
+        world.add(Box::new(Sphere::new_moving(
+            Point3::new(-1.0, 3.0, 0.0),
+            Point3::new(1.0, 3.0, 0.0),
+            0.3,
+            Material::diffuse_light(Color::new(1.0, 1.0, 1.0), 50.0),
+        )));
+        world
+    }
+
+    // Mean and variance of the red channel of the radiance along a ray looking down at the
+    // floor, at random times
+    fn estimate(world: &dyn Hittable, lights: &LightList, samples: usize) -> (f64, f64) {
+        let mut camera = Camera::new();
+        camera.background = Some(Color::new(0.0, 0.0, 0.0));
+
+        let mut rng = rand::thread_rng();
+        let (mut sum, mut sum_squares) = (0.0, 0.0);
+        for _ in 0..samples {
+            let r = Ray::with_time(
+                Point3::new(0.0, 1.0, -1.0),
+                Vec3::new(0.0, -1.0, 1.0),
+                rng.gen(),
+            );
+            let radiance = camera.ray_color(&r, 4, world, lights, None).x();
+            sum += radiance;
+            sum_squares += radiance * radiance;
+        }
+
+        let mean = sum / samples as f64;
+        (mean, sum_squares / samples as f64 - mean * mean)
+    }
+
+    #[test]
+    fn light_sampling_matches_scattering_alone() {
+        let world = scene();
+        let lights = LightList::new(&world);
+        assert_eq!(lights.len(), 1);
+
+        let (scattered_mean, scattered_variance) =
+            estimate(&world, &LightList::default(), 1_000_000);
+        let (mis_mean, mis_variance) = estimate(&world, &lights, 20_000);
+
+        let relative_error = (mis_mean - scattered_mean).abs() / scattered_mean;
+        assert!(
+            relative_error < 0.05,
+            "with light sampling {mis_mean}, scattering alone {scattered_mean}"
+        );
+        assert!(mis_variance < 0.1 * scattered_variance);
+    }
+}
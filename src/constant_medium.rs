@@ -0,0 +1,157 @@
+use std::borrow::Cow;
+
+use rand::Rng;
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+// Participating medium of constant density filling a closed boundary object, such as smoke or
+// fog. Rays passing through the medium scatter at a random distance that depends on the
+// density, with an isotropic phase function.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::Isotropic { albedo },
+        }
+    }
+
+    // Part of the ray over the interval that lies inside the boundary, if any
+    fn span(&self, r: &Ray, ray_t: Interval) -> Option<(f64, f64)> {
+        // Find where the ray enters and leaves the boundary along the whole line, so rays that
+        // start inside the medium are handled too
+        let rec1 = self.boundary.hit(r, Interval::UNIVERSE)?;
+        let rec2 = self
+            .boundary
+            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY))?;
+
+        let t_enter = rec1.t.max(ray_t.min).max(0.0);
+        let t_exit = rec2.t.min(ray_t.max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        Some((t_enter, t_exit))
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (t_enter, t_exit) = self.span(r, ray_t)?;
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * rand::thread_rng().gen::<f64>().ln();
+
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+
+        Some(HitRecord {
+            p: r.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
+            mat: Cow::Borrowed(&self.phase_function),
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face: true, // also arbitrary
+            tangent: Vec3::new(0.0, 0.0, 0.0),
+            color: Color::new(1.0, 1.0, 1.0),
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn shadow_hit(&self, _r: &Ray, _ray_t: Interval) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    // Constant density attenuates exponentially with the distance travelled inside
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        self.span(r, ray_t).map_or(1.0, |(t_enter, t_exit)| {
+            let distance_inside_boundary = (t_exit - t_enter) * r.direction().length();
+            (distance_inside_boundary / self.neg_inv_density).exp()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sphere::Sphere, vec3::Point3};
+
+    // Unit sphere of fog with density 2
+    fn fog() -> ConstantMedium {
+        let boundary = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::lambertian(Color::new(0.5, 0.5, 0.5)),
+        );
+        ConstantMedium::new(Box::new(boundary), 2.0, Color::new(0.8, 0.8, 0.8))
+    }
+
+    #[test]
+    fn transmittance_follows_the_distance_inside() {
+        let fog = fog();
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        // Through the full diameter from outside
+        let r = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((fog.transmittance(&r, ray_t) - (-4.0f64).exp()).abs() < 1e-9);
+
+        // From the center, only the radius ahead of the ray counts
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        assert!(
+            (fog.transmittance(&r, Interval::new(0.0, f64::INFINITY)) - (-2.0f64).exp()).abs()
+                < 1e-9
+        );
+
+        // Stopping halfway out, as a shadow ray to a light inside the fog would
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!((fog.transmittance(&r, Interval::new(0.0, 0.5)) - (-1.0f64).exp()).abs() < 1e-9);
+
+        let r = Ray::new(Point3::new(-5.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(fog.transmittance(&r, ray_t), 1.0);
+        assert!(fog.shadow_hit(&r, ray_t).is_none());
+    }
+
+    #[test]
+    fn rays_starting_inside_scatter_ahead_of_the_origin() {
+        let fog = fog();
+        let origin = Point3::new(0.5, 0.0, 0.0);
+        let r = Ray::new(origin, Vec3::new(1.0, 0.0, 0.0));
+
+        // The ray has 0.5 of fog left before it exits
+        let trials = 20000;
+        let mut scattered = 0;
+        for _ in 0..trials {
+            if let Some(rec) = fog.hit(&r, Interval::new(0.0, f64::INFINITY)) {
+                assert!(rec.t >= 0.0 && rec.t <= 0.5);
+                assert!((rec.p - r.at(rec.t)).length() < 1e-12);
+                scattered += 1;
+            }
+        }
+
+        let expected = 1.0 - (-1.0f64).exp();
+        let fraction = scattered as f64 / trials as f64;
+        assert!((fraction - expected).abs() < 0.02, "{} scattered", fraction);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    ray::Ray,
+};
+
+// Constructive solid geometry combines two closed objects by classifying the spans of a ray
+// inside each of them. Surfaces of the result keep the material of the operand they came from.
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Operation::Union => in_a || in_b,
+            Operation::Intersection => in_a && in_b,
+            Operation::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Points inside either a or b
+pub struct CsgUnion {
+    a: Box<dyn Hittable>,
+    b: Box<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl CsgUnion {
+    pub fn new(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        let bbox = Aabb::surrounding(&a.bounding_box(), &b.bounding_box());
+        CsgUnion { a, b, bbox }
+    }
+}
+
+impl Hittable for CsgUnion {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        first_hit(self.hit_intervals(r), ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        combine(Operation::Union, self.a.as_ref(), self.b.as_ref(), r)
+    }
+}
+
+// Points inside both a and b
+pub struct CsgIntersection {
+    a: Box<dyn Hittable>,
+    b: Box<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl CsgIntersection {
+    pub fn new(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        let box_a = a.bounding_box();
+        let box_b = b.bounding_box();
+        let overlap =
+            |i: &Interval, j: &Interval| Interval::new(i.min.max(j.min), i.max.min(j.max));
+
+        let bbox = Aabb::new(
+            overlap(&box_a.x, &box_b.x),
+            overlap(&box_a.y, &box_b.y),
+            overlap(&box_a.z, &box_b.z),
+        );
+        CsgIntersection { a, b, bbox }
+    }
+}
+
+impl Hittable for CsgIntersection {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        first_hit(self.hit_intervals(r), ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        combine(Operation::Intersection, self.a.as_ref(), self.b.as_ref(), r)
+    }
+}
+
+// Points inside a but not inside b. The carved surfaces take b's material.
+pub struct CsgDifference {
+    a: Box<dyn Hittable>,
+    b: Box<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl CsgDifference {
+    pub fn new(a: Box<dyn Hittable>, b: Box<dyn Hittable>) -> Self {
+        let bbox = a.bounding_box();
+        CsgDifference { a, b, bbox }
+    }
+}
+
+impl Hittable for CsgDifference {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        first_hit(self.hit_intervals(r), ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        combine(Operation::Difference, self.a.as_ref(), self.b.as_ref(), r)
+    }
+}
+
+// Returns the nearest boundary of the combined solid within ray_t
+fn first_hit<'a>(
+    intervals: Vec<(HitRecord<'a>, HitRecord<'a>)>,
+    ray_t: Interval,
+) -> Option<HitRecord<'a>> {
+    intervals
+        .into_iter()
+        .flat_map(|(entry, exit)| [entry, exit])
+        .find(|rec| ray_t.surrounds(rec.t))
+}
+
+fn combine<'a>(
+    op: Operation,
+    a: &'a dyn Hittable,
+    b: &'a dyn Hittable,
+    r: &Ray,
+) -> Vec<(HitRecord<'a>, HitRecord<'a>)> {
+    let intervals_a = a.hit_intervals(r);
+    if intervals_a.is_empty() && !matches!(op, Operation::Union) {
+        return Vec::new();
+    }
+    let intervals_b = b.hit_intervals(r);
+
+    // Each crossing of an operand surface is an event: (record, crosses a, enters the operand)
+    let mut events = Vec::with_capacity(2 * (intervals_a.len() + intervals_b.len()));
+    for (intervals, is_a) in [(intervals_a, true), (intervals_b, false)] {
+        for (entry, exit) in intervals {
+            events.push((entry, is_a, true));
+            events.push((exit, is_a, false));
+        }
+    }
+    events.sort_by(|e1, e2| e1.0.t.total_cmp(&e2.0.t));
+
+    // Sweep along the ray, emitting a boundary of the result wherever its inside state changes
+    let mut in_a = false;
+    let mut in_b = false;
+    let mut result = Vec::new();
+    let mut entry: Option<HitRecord> = None;
+
+    for (mut rec, is_a, entering) in events {
+        let was_inside = op.inside(in_a, in_b);
+        if is_a {
+            in_a = entering;
+        } else {
+            in_b = entering;
+        }
+        let is_inside = op.inside(in_a, in_b);
+
+        if was_inside == is_inside {
+            continue;
+        }
+
+        // The normal already faces against the ray. Whether the surface is a front face of the
+        // result depends only on whether the ray enters the result there, e.g. leaving b enters
+        // a difference.
+        rec.front_face = is_inside;
+        if is_inside {
+            entry = Some(rec);
+        } else if let Some(enter) = entry.take() {
+            // Coincident surfaces, e.g. entering a and b at the same point of a difference,
+            // leave an empty span that isn't a boundary
+            if rec.t > enter.t {
+                result.push((enter, rec));
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        material::Material,
+        sphere::Sphere,
+        vec3::{Point3, Vec3},
+    };
+
+    // Unit spheres centered at x = -0.5 and x = 0.5, overlapping between x = -0.5 and 0.5
+    fn spheres() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+        let mat = Material::lambertian(Color::new(0.5, 0.5, 0.5));
+        (
+            Box::new(Sphere::new(Point3::new(-0.5, 0.0, 0.0), 1.0, mat.clone())),
+            Box::new(Sphere::new(Point3::new(0.5, 0.0, 0.0), 1.0, mat)),
+        )
+    }
+
+    // Ray along x starting at x = -5, so t = x + 5
+    fn ray() -> Ray {
+        Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    fn assert_spans(object: &dyn Hittable, expected: &[(f64, f64)]) {
+        let spans = object.hit_intervals(&ray());
+        assert_eq!(spans.len(), expected.len());
+        for ((entry, exit), &(t0, t1)) in spans.iter().zip(expected) {
+            assert!(
+                (entry.t - t0).abs() < 1e-9,
+                "entry at {}, expected {}",
+                entry.t,
+                t0
+            );
+            assert!(
+                (exit.t - t1).abs() < 1e-9,
+                "exit at {}, expected {}",
+                exit.t,
+                t1
+            );
+            assert!(entry.front_face && !exit.front_face);
+
+            // Normals face against the ray
+            let against = Vec3::new(-1.0, 0.0, 0.0);
+            assert!((entry.normal - against).length() < 1e-9);
+            assert!((exit.normal - against).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn union_spans_both_spheres() {
+        let (a, b) = spheres();
+        let union = CsgUnion::new(a, b);
+        assert_spans(&union, &[(3.5, 6.5)]);
+
+        // From inside the overlap, the first boundary is where b ends
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = union.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn intersection_keeps_the_overlap() {
+        let (a, b) = spheres();
+        let intersection = CsgIntersection::new(a, b);
+        assert_spans(&intersection, &[(4.5, 5.5)]);
+
+        let rec = intersection
+            .hit(&ray(), Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!(rec.front_face);
+
+        // Passing through a alone misses
+        let r = Ray::new(Point3::new(-1.2, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(intersection
+            .hit(&r, Interval::new(0.001, f64::INFINITY))
+            .is_none());
+    }
+
+    #[test]
+    fn difference_removes_the_overlap_from_a() {
+        let (a, b) = spheres();
+        let difference = CsgDifference::new(a, b);
+
+        // Leaving the result where the ray enters b is a back face
+        assert_spans(&difference, &[(3.5, 4.5)]);
+
+        // From inside b, the ray first meets a's remaining part where it leaves b, from outside
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = difference
+            .hit(&r, Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert!((rec.t - 0.5).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+
+        // Through the overlap only, nothing is left, including where both surfaces coincide
+        for x in [0.0, 0.1] {
+            let r = Ray::new(Point3::new(x, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+            assert!(difference
+                .hit(&r, Interval::new(0.001, f64::INFINITY))
+                .is_none());
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+// Limit on the number of times a curve is halved while searching for an intersection
+const MAX_SUBDIVISION_DEPTH: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveKind {
+    // Flat strip that always faces the ray, for strands too thin to see their roundness
+    Flat,
+    // Intersected as a flat strip but shaded, and placed in depth, like a round tube
+    Cylinder,
+}
+
+// Thin cubic Bezier curve with a width varying linearly along its length, for hair, fur and
+// grass. Rays are intersected with the curve directly by recursively splitting it until the
+// pieces are close to straight. u runs along the curve from 0 to 1 and v across the strand
+// from 0 to 1, and the hit record's tangent follows the curve.
+pub struct Curve {
+    control_points: [Point3; 4],
+    widths: [f64; 2],
+    u_range: [f64; 2],
+    kind: CurveKind,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Curve {
+    pub fn new(
+        control_points: [Point3; 4],
+        width0: f64,
+        width1: f64,
+        kind: CurveKind,
+        mat: Material,
+    ) -> Self {
+        Self::segment(&control_points, [width0, width1], [0.0, 1.0], kind, mat)
+    }
+
+    // The part of the curve between the parameters u0 and u1
+    fn segment(
+        control_points: &[Point3; 4],
+        widths: [f64; 2],
+        u_range: [f64; 2],
+        kind: CurveKind,
+        mat: Material,
+    ) -> Self {
+        let [u0, u1] = u_range;
+        let control_points = [
+            blossom(control_points, u0, u0, u0),
+            blossom(control_points, u0, u0, u1),
+            blossom(control_points, u0, u1, u1),
+            blossom(control_points, u1, u1, u1),
+        ];
+        let widths = [
+            lerp(u0, widths[0], widths[1]),
+            lerp(u1, widths[0], widths[1]),
+        ];
+
+        // The curve lies within the convex hull of its control points, so the box around them
+        // padded by half the width encloses the strand
+        let half_width = 0.5 * widths[0].max(widths[1]);
+        let pad = Vec3::new(half_width, half_width, half_width);
+        let bbox = control_points.iter().fold(Aabb::EMPTY, |bbox, &p| {
+            Aabb::surrounding(&bbox, &Aabb::from_points(p - pad, p + pad))
+        });
+
+        Curve {
+            control_points,
+            widths,
+            u_range,
+            kind,
+            mat,
+            bbox,
+        }
+    }
+
+    fn width(&self, w: f64) -> f64 {
+        lerp(w, self.widths[0], self.widths[1])
+    }
+
+    // Search the part of the curve between the segment parameters w0 and w1, given by its
+    // control points in ray space, for the intersection closest to the ray origin. Returns the
+    // segment parameter and ray space depth of the hit.
+    fn recursive_hit(
+        &self,
+        cp: &[Vec3; 4],
+        w0: f64,
+        w1: f64,
+        depth: usize,
+        z_range: &mut Interval,
+    ) -> Option<(f64, f64)> {
+        // The ray runs along the z axis of ray space, so it can only hit pieces whose padded
+        // bounds contain the z axis
+        let half_width = 0.5 * self.width(w0).max(self.width(w1));
+        let pad = Vec3::new(half_width, half_width, half_width);
+        let bounds = cp.iter().fold(Aabb::EMPTY, |bbox, &p| {
+            Aabb::surrounding(&bbox, &Aabb::from_points(p - pad, p + pad))
+        });
+        if !bounds.x.contains(0.0)
+            || !bounds.y.contains(0.0)
+            || bounds.z.max < z_range.min
+            || bounds.z.min > z_range.max
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let (first, second) = split_bezier(cp);
+            let w_mid = 0.5 * (w0 + w1);
+            let first_hit = self.recursive_hit(&first, w0, w_mid, depth - 1, z_range);
+            let second_hit = self.recursive_hit(&second, w_mid, w1, depth - 1, z_range);
+            return second_hit.or(first_hit);
+        }
+
+        // Treat the piece as a line segment. Skip rays that pass before its start or after its
+        // end, measured perpendicular to the curve there, so neighboring pieces don't overlap.
+        let start = Vec3::new(cp[0].x(), cp[0].y(), 0.0);
+        let end = Vec3::new(cp[3].x(), cp[3].y(), 0.0);
+        if -start.dot(&(cp[1] - cp[0])) < 0.0 || -end.dot(&(cp[2] - cp[3])) < 0.0 {
+            return None;
+        }
+
+        // Parameter of the point on the segment closest to the ray
+        let segment = end - start;
+        let denom = segment.length_squared();
+        if denom == 0.0 {
+            return None;
+        }
+        let s = (-start.dot(&segment) / denom).clamp(0.0, 1.0);
+        let w = lerp(s, w0, w1);
+
+        let pc = eval_bezier(cp, s).0;
+        let hit_width = self.width(w);
+        if pc.x() * pc.x() + pc.y() * pc.y() > 0.25 * hit_width * hit_width {
+            return None;
+        }
+        if !z_range.surrounds(pc.z()) {
+            return None;
+        }
+
+        z_range.max = pc.z();
+        Some((w, pc.z()))
+    }
+}
+
+impl Hittable for Curve {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // Transform the control points to ray space: origin at the ray origin and z along the
+        // ray, where z measures distance along the ray
+        let length = r.direction().length();
+        let onb = Onb::new(&r.direction());
+        let cp = self.control_points.map(|p| onb.to_local(&(p - r.origin())));
+
+        // Choose the subdivision depth from the curvature, so the final pieces deviate from
+        // straight lines by a small fraction of the width
+        let l0 = (0..2)
+            .map(|i| {
+                let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+                d.x().abs().max(d.y().abs()).max(d.z().abs())
+            })
+            .fold(0.0, f64::max);
+        let epsilon = 0.05 * self.widths[0].max(self.widths[1]);
+        let depth = if l0 > 0.0 && epsilon > 0.0 {
+            let r0 = (std::f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * epsilon)).log2();
+            (r0.max(0.0) as usize / 2).min(MAX_SUBDIVISION_DEPTH)
+        } else {
+            0
+        };
+
+        let mut z_range = Interval::new(ray_t.min * length, ray_t.max * length);
+        let (w, z) = self.recursive_hit(&cp, 0.0, 1.0, depth, &mut z_range)?;
+        let mut t = z / length;
+
+        // Build the shading frame from the curve's tangent and the direction back to the ray
+        let (center, derivative) = eval_bezier(&self.control_points, w);
+        let tangent = derivative.unit_vector();
+        let unit_direction = r.direction() / length;
+        let facing = -(unit_direction - unit_direction.dot(&tangent) * tangent);
+        if facing.length_squared() < 1e-12 {
+            // The ray runs along the strand
+            return None;
+        }
+        let facing = facing.unit_vector();
+        let side = tangent.cross(&facing);
+
+        // Signed distance across the strand from its center line, as a fraction of the width
+        let hit_width = self.width(w);
+        let offset = (r.at(t) - center).dot(&side) / hit_width;
+
+        let outward_normal = match self.kind {
+            CurveKind::Flat => facing,
+            CurveKind::Cylinder => {
+                // Bend the normal around the tube and move the hit out to its surface
+                let s = (2.0 * offset).clamp(-1.0, 1.0);
+                let c = (1.0 - s * s).sqrt();
+                let t_surface = t - 0.5 * hit_width * c / length;
+                if ray_t.surrounds(t_surface) {
+                    t = t_surface;
+                }
+                s * side + c * facing
+            }
+        };
+
+        let mut rec = HitRecord::new(r.at(t), t, r, &outward_normal, &self.mat);
+        rec.u = lerp(w, self.u_range[0], self.u_range[1]);
+        rec.v = (0.5 + offset).clamp(0.0, 1.0);
+        rec.tangent = tangent;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// Returns a curve split into the given number of pieces along its length. Each piece gets its
+// own bounding box, which fit the strand much more tightly than one box around a long curve.
+pub fn make_curve(
+    control_points: [Point3; 4],
+    width0: f64,
+    width1: f64,
+    kind: CurveKind,
+    mat: Material,
+    segments: usize,
+) -> HittableList {
+    let mut pieces = HittableList::new();
+    let segments = segments.max(1);
+
+    for i in 0..segments {
+        let u_range = [i as f64 / segments as f64, (i + 1) as f64 / segments as f64];
+        pieces.add(Box::new(Curve::segment(
+            &control_points,
+            [width0, width1],
+            u_range,
+            kind,
+            mat.clone(),
+        )));
+    }
+
+    pieces
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+// Point and derivative of the cubic Bezier curve at u
+fn eval_bezier(cp: &[Vec3; 4], u: f64) -> (Vec3, Vec3) {
+    let cp1 = [0, 1, 2].map(|i| (1.0 - u) * cp[i] + u * cp[i + 1]);
+    let cp2 = [0, 1].map(|i| (1.0 - u) * cp1[i] + u * cp1[i + 1]);
+    let derivative = cp2[1] - cp2[0];
+
+    // At the ends the derivative vanishes if control points coincide, so fall back to the
+    // direction between the first and last control points
+    let derivative = if derivative.length_squared() > 0.0 {
+        3.0 * derivative
+    } else {
+        cp[3] - cp[0]
+    };
+
+    ((1.0 - u) * cp2[0] + u * cp2[1], derivative)
+}
+
+// Split the curve at its midpoint with de Casteljau's algorithm
+fn split_bezier(cp: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let cp1 = [0, 1, 2].map(|i| 0.5 * (cp[i] + cp[i + 1]));
+    let cp2 = [0, 1].map(|i| 0.5 * (cp1[i] + cp1[i + 1]));
+    let mid = 0.5 * (cp2[0] + cp2[1]);
+
+    ([cp[0], cp1[0], cp2[0], mid], [mid, cp2[1], cp1[2], cp[3]])
+}
+
+// Blossom of the cubic Bezier curve, which gives the control points of any piece of the curve
+fn blossom(cp: &[Vec3; 4], u0: f64, u1: f64, u2: f64) -> Vec3 {
+    let a = [0, 1, 2].map(|i| (1.0 - u0) * cp[i] + u0 * cp[i + 1]);
+    let b = [0, 1].map(|i| (1.0 - u1) * a[i] + u1 * a[i + 1]);
+    (1.0 - u2) * b[0] + u2 * b[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn curve(control_points: [Point3; 4], kind: CurveKind) -> Curve {
+        let mat = Material::lambertian(Color::new(0.5, 0.5, 0.5));
+        Curve::new(control_points, 0.2, 0.2, kind, mat)
+    }
+
+    // Straight strand along x from -1 to 1
+    fn straight(kind: CurveKind) -> Curve {
+        curve(
+            [
+                Point3::new(-1.0, 0.0, 0.0),
+                Point3::new(-1.0 / 3.0, 0.0, 0.0),
+                Point3::new(1.0 / 3.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+            ],
+            kind,
+        )
+    }
+
+    fn hit(curve: &Curve, origin: Point3) -> Option<HitRecord<'_>> {
+        let r = Ray::new(origin, Vec3::new(0.0, 0.0, -1.0));
+        curve.hit(&r, Interval::new(0.0, f64::INFINITY))
+    }
+
+    #[test]
+    fn flat_strip_faces_the_ray() {
+        let strip = straight(CurveKind::Flat);
+
+        let rec = hit(&strip, Point3::new(0.3, 0.05, 5.0)).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        assert!((rec.tangent - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((rec.u - 0.65).abs() < 1e-9);
+        assert!((rec.v - 0.25).abs() < 1e-9);
+
+        assert!(hit(&strip, Point3::new(0.3, 0.15, 5.0)).is_none());
+        assert!(hit(&strip, Point3::new(1.2, 0.0, 5.0)).is_none());
+    }
+
+    #[test]
+    fn cylinder_strand_is_shaded_like_a_tube() {
+        let tube = straight(CurveKind::Cylinder);
+
+        // A quarter of the width off center, the tube's surface is sqrt(0.75) of its radius up
+        let rec = hit(&tube, Point3::new(0.3, 0.05, 5.0)).unwrap();
+        let c = 0.75f64.sqrt();
+        assert!((rec.t - (5.0 - 0.1 * c)).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 0.5, c)).length() < 1e-9);
+    }
+
+    #[test]
+    fn curved_strand_is_hit_at_its_apex() {
+        let arch = curve(
+            [
+                Point3::new(-1.0, 0.0, 0.0),
+                Point3::new(-1.0, 2.0, 1.0),
+                Point3::new(1.0, 2.0, 1.0),
+                Point3::new(1.0, 0.0, 0.0),
+            ],
+            CurveKind::Flat,
+        );
+
+        // The apex at u = 0.5 is (0, 1.5, 0.75), where the curve runs along x
+        let rec = hit(&arch, Point3::new(0.0, 1.5, 5.0)).unwrap();
+        assert!((rec.t - 4.25).abs() < 1e-3);
+        assert!((rec.u - 0.5).abs() < 1e-3);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-3);
+        assert!(hit(&arch, Point3::new(0.0, 1.7, 5.0)).is_none());
+    }
+}
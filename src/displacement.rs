@@ -0,0 +1,253 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    heightfield::HeightGrid,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    mesh::{MeshBuffers, TriangleMesh},
+    ray::Ray,
+    utils::PI,
+    vec3::{Point3, Vec3},
+};
+
+// Surface to be displaced, described by its position and normal over (u, v) in [0, 1]
+pub enum BaseSurface {
+    // Parallelogram with corner q and edges u and v, matching Quad
+    Patch { q: Point3, u: Vec3, v: Vec3 },
+    // Sphere with the same (u, v) mapping as Sphere: u around the y axis from -x and v from the
+    // bottom pole to the top
+    Sphere { center: Point3, radius: f64 },
+}
+
+impl BaseSurface {
+    // Number of grid steps along u and v so that no edge is longer than edge_length
+    fn resolution(&self, edge_length: f64) -> (usize, usize) {
+        let steps = |length: f64| ((length / edge_length).ceil() as usize).max(1);
+        match self {
+            BaseSurface::Patch { u, v, .. } => (steps(u.length()), steps(v.length())),
+            BaseSurface::Sphere { radius, .. } => {
+                (steps(2.0 * PI * radius).max(3), steps(PI * radius).max(2))
+            }
+        }
+    }
+
+    // Point and unit normal at grid vertex (i, j) of an nu by nv grid. Sphere vertices on the
+    // seam and at the poles are computed exactly alike, so displaced copies stay together.
+    fn vertex(&self, i: usize, j: usize, nu: usize, nv: usize) -> (Point3, Vec3) {
+        let (s, t) = (i as f64 / nu as f64, j as f64 / nv as f64);
+        match self {
+            BaseSurface::Patch { q, u, v } => (*q + s * *u + t * *v, u.cross(v).unit_vector()),
+            BaseSurface::Sphere { center, radius } => {
+                let phi = 2.0 * PI * (i % nu) as f64 / nu as f64;
+                let (sin_theta, cos_theta) = match j {
+                    0 => (0.0, 1.0),
+                    j if j == nv => (0.0, -1.0),
+                    _ => (PI * t).sin_cos(),
+                };
+                // Adding zero turns the negative zeros at the poles into positive ones
+                let normal = Vec3::new(
+                    -phi.cos() * sin_theta + 0.0,
+                    -cos_theta,
+                    phi.sin() * sin_theta + 0.0,
+                );
+                (*center + *radius * normal, normal)
+            }
+        }
+    }
+
+    // Texture coordinates used to look up the displacement. Coordinates that land on the same
+    // point of a sphere are mapped to the same place in the image.
+    fn lookup_uv(&self, i: usize, j: usize, nu: usize, nv: usize) -> (f64, f64) {
+        let (s, t) = (i as f64 / nu as f64, j as f64 / nv as f64);
+        match self {
+            BaseSurface::Patch { .. } => (s, t),
+            BaseSurface::Sphere { .. } if j == 0 || j == nv => (0.0, t),
+            BaseSurface::Sphere { .. } => ((i % nu) as f64 / nu as f64, t),
+        }
+    }
+}
+
+// Offset for an undisplaced point and its (u, v)
+pub type DisplacementFn = Box<dyn Fn(&Point3, f64, f64) -> f64>;
+
+// Scalar offset along the base surface's normal
+pub enum Displacement {
+    // Function returning the offset directly
+    Procedural(DisplacementFn),
+    // Greyscale image sampled at (u, v), with heights in [0, 1] multiplied by scale. Like
+    // ImageTexture, v runs from the bottom row of the image to the top, so a color map on the
+    // same coordinates lines up with the displacement.
+    Image { grid: HeightGrid, scale: f64 },
+}
+
+impl Displacement {
+    fn offset(&self, p: &Point3, u: f64, v: f64) -> f64 {
+        match self {
+            Displacement::Procedural(f) => f(p, u, v),
+            // Grid rows run from the top of the image down
+            Displacement::Image { grid, scale } => scale * grid.sample(u, 1.0 - v),
+        }
+    }
+}
+
+// Base surface tessellated into triangles no longer than a target edge length, with each vertex
+// moved along the surface normal by a displacement. Shading normals are recomputed from the
+// displaced triangles, so the detail shows in the lighting as well as the silhouette.
+pub struct DisplacedSurface {
+    mesh: TriangleMesh,
+}
+
+impl DisplacedSurface {
+    pub fn new(
+        base: BaseSurface,
+        displacement: Displacement,
+        edge_length: f64,
+        mat: Material,
+    ) -> Self {
+        assert!(edge_length > 0.0, "edge length must be positive");
+
+        let (nu, nv) = base.resolution(edge_length);
+        let mut positions = Vec::with_capacity((nu + 1) * (nv + 1));
+        let mut uvs = Vec::with_capacity((nu + 1) * (nv + 1));
+        for j in 0..=nv {
+            for i in 0..=nu {
+                let (p, normal) = base.vertex(i, j, nu, nv);
+                let (lookup_u, lookup_v) = base.lookup_uv(i, j, nu, nv);
+                positions.push(p + displacement.offset(&p, lookup_u, lookup_v) * normal);
+                uvs.push([i as f64 / nu as f64, j as f64 / nv as f64]);
+            }
+        }
+
+        let index = |i: usize, j: usize| j * (nu + 1) + i;
+        let mut triangles = Vec::with_capacity(2 * nu * nv);
+        for j in 0..nv {
+            for i in 0..nu {
+                triangles.push([index(i, j), index(i + 1, j), index(i + 1, j + 1)]);
+                triangles.push([index(i, j), index(i + 1, j + 1), index(i, j + 1)]);
+            }
+        }
+
+        let normals = vertex_normals(&positions, &triangles);
+        let mut buffers = MeshBuffers::new(positions);
+        buffers.normals = normals;
+        buffers.uvs = uvs;
+
+        DisplacedSurface {
+            mesh: TriangleMesh::new(Arc::new(buffers), triangles, mat),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.mesh.triangle_count()
+    }
+}
+
+impl Hittable for DisplacedSurface {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.mesh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.mesh.bounding_box()
+    }
+}
+
+// Area weighted average of the normals of the triangles around each vertex. Vertices at the
+// same position share their normal, so seams and poles of the grid shade smoothly.
+fn vertex_normals(positions: &[Point3], triangles: &[[usize; 3]]) -> Vec<Vec3> {
+    let key = |p: &Point3| [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+    let mut sums: HashMap<[u64; 3], Vec3> = HashMap::new();
+
+    for &[i0, i1, i2] in triangles {
+        let (p0, p1, p2) = (positions[i0], positions[i1], positions[i2]);
+        let face_normal = (p1 - p0).cross(&(p2 - p0));
+        for p in [p0, p1, p2] {
+            *sums.entry(key(&p)).or_insert(Vec3::new(0.0, 0.0, 0.0)) += face_normal;
+        }
+    }
+
+    positions
+        .iter()
+        .map(|p| {
+            let sum = sums[&key(p)];
+            if sum.length_squared() > 0.0 {
+                sum.unit_vector()
+            } else {
+                sum
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        texture::{ImageTexture, Texture},
+    };
+
+    // 2x2 greyscale image, white along the top row and black along the bottom
+    const IMAGE: &[u8] = b"P2 2 2 255\n255 255\n0 0\n";
+
+    fn hit_from_above(surface: &DisplacedSurface, x: f64, y: f64) -> HitRecord<'_> {
+        let r = Ray::new(Point3::new(x, y, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        surface
+            .hit(&r, Interval::new(0.0, f64::INFINITY))
+            .expect("ray missed the displaced patch")
+    }
+
+    #[test]
+    fn image_displacement_lines_up_with_image_texture() {
+        let texture = ImageTexture::decode(IMAGE).unwrap();
+        // Same rows as the image, top first
+        let grid = HeightGrid::new(2, 2, vec![1.0, 1.0, 0.0, 0.0]);
+        let surface = DisplacedSurface::new(
+            BaseSurface::Patch {
+                q: Point3::new(0.0, 0.0, 0.0),
+                u: Vec3::new(1.0, 0.0, 0.0),
+                v: Vec3::new(0.0, 1.0, 0.0),
+            },
+            Displacement::Image { grid, scale: 1.0 },
+            0.05,
+            Material::lambertian(Color::new(0.5, 0.5, 0.5)),
+        );
+
+        // The height rises with v toward the white top row, and the bright half of the texture
+        // is the raised half of the patch
+        for y in [0.1, 0.3, 0.7, 0.9] {
+            let rec = hit_from_above(&surface, 0.5, y);
+            let color = texture.value(rec.u, rec.v, &rec.p);
+            assert!(
+                (rec.p.z() - rec.v).abs() < 0.02,
+                "height {} at v = {}",
+                rec.p.z(),
+                rec.v
+            );
+            assert_eq!(color.x() > 0.5, rec.p.z() > 0.5);
+        }
+    }
+
+    #[test]
+    fn procedural_displacement_moves_along_the_normal() {
+        let surface = DisplacedSurface::new(
+            BaseSurface::Sphere {
+                center: Point3::new(0.0, 0.0, 0.0),
+                radius: 1.0,
+            },
+            Displacement::Procedural(Box::new(|_, _, _| 0.5)),
+            0.05,
+            Material::lambertian(Color::new(0.5, 0.5, 0.5)),
+        );
+
+        // A constant offset turns the sphere into a larger one, closed at the seam and poles
+        for (x, y) in [(0.0, 0.0), (0.3, -0.4), (0.0, 1.2), (-1.0, 0.5)] {
+            let rec = hit_from_above(&surface, x, y);
+            let radius = rec.p.length();
+            assert!((radius - 1.5).abs() < 1e-2, "radius {}", radius);
+            assert!(rec.normal.dot(&(rec.p / radius)) > 0.99);
+        }
+    }
+}
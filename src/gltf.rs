@@ -0,0 +1,1119 @@
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde_json::Value;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable_list::HittableList,
+    material::Material,
+    mesh::{MeshBuffers, TriangleMesh},
+    texture::{ImageTexture, SolidColor, Texture},
+    transform::Mat4,
+    vec3::{Point3, Vec3},
+};
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A; // "JSON"
+const GLB_CHUNK_BIN: u32 = 0x004E_4942; // "BIN\0"
+
+// Guards against cyclic node graphs in malformed files
+const MAX_NODE_DEPTH: usize = 256;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Format {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            GltfError::Json { path, source } => write!(f, "{}: {}", path.display(), source),
+            GltfError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for GltfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GltfError::Io { source, .. } => Some(source),
+            GltfError::Json { source, .. } => Some(source),
+            GltfError::Format { .. } => None,
+        }
+    }
+}
+
+// Encoded image referenced by a material, either read from disk or from a buffer view
+pub struct GltfImage {
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+// PBR metallic-roughness parameters of a glTF material, along with the Material they map onto.
+// Texture fields hold indices into GltfScene::images.
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: Color,
+    pub alpha: f64,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub transmission: f64,
+    pub ior: f64,
+    pub base_color_texture: Option<usize>,
+    pub metallic_roughness_texture: Option<usize>,
+    pub material: Material,
+}
+
+impl GltfMaterial {
+    // Map the PBR parameters onto the closest Material variant:
+    // - transmissive or mostly transparent materials become Dielectric with the given ior
+    // - metallic materials become Metal, with the roughness as fuzz
+    // - everything else becomes Lambertian with the base color
+    // The base color texture, if any, is multiplied by the base color, and the green channel of
+    // the metallic-roughness texture by the roughness. Images we can't decode, such as JPEGs,
+    // fall back to the factors alone.
+    fn to_material(&self, images: &[GltfImage]) -> Material {
+        let albedo: Arc<dyn Texture> = match self
+            .base_color_texture
+            .and_then(|i| images.get(i))
+            .and_then(|image| ImageTexture::decode(&image.data).ok())
+        {
+            Some(texture) => Arc::new(texture.with_scale(self.base_color)),
+            None => Arc::new(SolidColor::new(self.base_color)),
+        };
+
+        if self.transmission > 0.5 || self.alpha < 0.5 {
+            Material::Dielectric {
+                refraction_index: self.ior,
+            }
+        } else if self.metallic >= 0.5 {
+            let roughness = self.roughness.clamp(0.0, 1.0);
+            let fuzz: Arc<dyn Texture> = match self
+                .metallic_roughness_texture
+                .and_then(|i| images.get(i))
+                .and_then(|image| ImageTexture::decode_linear(&image.data).ok())
+            {
+                Some(image) => Arc::new(RoughnessTexture { image, roughness }),
+                None => Arc::new(SolidColor::from_rgb(roughness, roughness, roughness)),
+            };
+            Material::Metal { albedo, fuzz }
+        } else {
+            Material::Lambertian { albedo }
+        }
+    }
+}
+
+// Roughness read from the green channel of a metallic-roughness texture, scaled by the
+// material's roughness factor and spread over all channels to drive Metal fuzz
+struct RoughnessTexture {
+    image: ImageTexture,
+    roughness: f64,
+}
+
+impl Texture for RoughnessTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let roughness = self.roughness * self.image.value(u, v, p).y();
+        Color::new(roughness, roughness, roughness)
+    }
+}
+
+// Perspective camera placed by its node's world transform
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub vfov: f64,                 // vertical field of view in degrees
+    pub aspect_ratio: Option<f64>, // None if the file leaves it to the viewport
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+}
+
+impl GltfCamera {
+    pub fn apply_to(&self, cam: &mut Camera) {
+        cam.vfov = self.vfov;
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            cam.aspect_ratio = aspect_ratio;
+        }
+        cam.lookfrom = self.lookfrom;
+        cam.lookat = self.lookat;
+        cam.vup = self.vup;
+    }
+}
+
+// Mesh primitive with its node's world transform baked into the vertex buffers
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub material: Option<usize>,
+    pub mesh: TriangleMesh,
+}
+
+// Contents of the default scene of a .gltf or .glb file
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub images: Vec<GltfImage>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl GltfScene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GltfError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|source| GltfError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let loader = GltfLoader::new(path, &data)?;
+        loader.load_scene()
+    }
+
+    pub fn into_hittable_list(self) -> HittableList {
+        let mut list = HittableList::new();
+        for mesh in self.meshes {
+            list.add(Box::new(mesh.mesh));
+        }
+        list
+    }
+}
+
+struct GltfLoader<'a> {
+    path: &'a Path,
+    base_dir: &'a Path,
+    json: Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> GltfLoader<'a> {
+    fn new(path: &'a Path, data: &[u8]) -> Result<Self, GltfError> {
+        let mut loader = GltfLoader {
+            path,
+            base_dir: path.parent().unwrap_or_else(|| Path::new("")),
+            json: Value::Null,
+            buffers: Vec::new(),
+        };
+
+        let mut glb_bin = None;
+        let json_bytes = if data.len() >= 4 && read_u32(data, 0) == Some(GLB_MAGIC) {
+            let (json, bin) = loader.split_glb(data)?;
+            glb_bin = bin;
+            json
+        } else {
+            data
+        };
+
+        loader.json = serde_json::from_slice(json_bytes).map_err(|source| GltfError::Json {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let version = loader.json["asset"]["version"].as_str().unwrap_or("");
+        if !version.starts_with("2.") {
+            return Err(loader.error(format!("unsupported glTF version '{}'", version)));
+        }
+
+        loader.load_buffers(glb_bin)?;
+        Ok(loader)
+    }
+
+    fn error(&self, message: impl Into<String>) -> GltfError {
+        GltfError::Format {
+            path: self.path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+
+    // Split a binary glTF container into its JSON chunk and optional BIN chunk
+    fn split_glb<'d>(&self, data: &'d [u8]) -> Result<(&'d [u8], Option<&'d [u8]>), GltfError> {
+        let version = read_u32(data, 4).ok_or_else(|| self.error("truncated GLB header"))?;
+        if version != 2 {
+            return Err(self.error(format!("unsupported GLB version {}", version)));
+        }
+        let length = read_u32(data, 8).ok_or_else(|| self.error("truncated GLB header"))? as usize;
+        let data = data
+            .get(..length)
+            .ok_or_else(|| self.error("GLB length exceeds file size"))?;
+
+        let mut json = None;
+        let mut bin = None;
+        let mut offset = 12;
+        while offset < data.len() {
+            let chunk_length = read_u32(data, offset)
+                .ok_or_else(|| self.error("truncated GLB chunk header"))?
+                as usize;
+            let chunk_type = read_u32(data, offset + 4)
+                .ok_or_else(|| self.error("truncated GLB chunk header"))?;
+            let chunk = data
+                .get(offset + 8..offset + 8 + chunk_length)
+                .ok_or_else(|| self.error("truncated GLB chunk"))?;
+
+            match chunk_type {
+                GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+                GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+                _ => {}
+            }
+            offset += 8 + chunk_length;
+        }
+
+        let json = json.ok_or_else(|| self.error("GLB file has no JSON chunk"))?;
+        Ok((json, bin))
+    }
+
+    fn load_buffers(&mut self, glb_bin: Option<&[u8]>) -> Result<(), GltfError> {
+        let buffers = array(&self.json["buffers"]).to_vec();
+        for (index, buffer) in buffers.iter().enumerate() {
+            let data = match buffer["uri"].as_str() {
+                Some(uri) => self.read_uri(uri)?,
+                None if index == 0 => glb_bin
+                    .ok_or_else(|| self.error("buffer 0 has no uri and there is no GLB BIN chunk"))?
+                    .to_vec(),
+                None => return Err(self.error(format!("buffer {} has no uri", index))),
+            };
+
+            let byte_length = buffer["byteLength"].as_u64().unwrap_or(0) as usize;
+            if data.len() < byte_length {
+                return Err(self.error(format!(
+                    "buffer {} is {} bytes, expected {}",
+                    index,
+                    data.len(),
+                    byte_length
+                )));
+            }
+            self.buffers.push(data);
+        }
+        Ok(())
+    }
+
+    // Read a data URI or a file relative to the glTF file
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, GltfError> {
+        if let Some(rest) = uri.strip_prefix("data:") {
+            let (_, payload) = rest
+                .split_once(";base64,")
+                .ok_or_else(|| self.error("only base64 data URIs are supported"))?;
+            return decode_base64(payload).ok_or_else(|| self.error("invalid base64 data URI"));
+        }
+
+        let file_path = self.base_dir.join(percent_decode(uri));
+        fs::read(&file_path).map_err(|source| GltfError::Io {
+            path: file_path,
+            source,
+        })
+    }
+
+    fn load_scene(&self) -> Result<GltfScene, GltfError> {
+        let images = self.load_images()?;
+        let materials = self.load_materials(&images)?;
+        let mut scene = GltfScene {
+            meshes: Vec::new(),
+            materials,
+            images,
+            cameras: Vec::new(),
+        };
+
+        let root_nodes: Vec<usize> = match self.json["scenes"].as_array() {
+            Some(scenes) if !scenes.is_empty() => {
+                let scene_index = self.json["scene"].as_u64().unwrap_or(0) as usize;
+                let root = scenes
+                    .get(scene_index)
+                    .ok_or_else(|| self.error(format!("scene {} does not exist", scene_index)))?;
+                array(&root["nodes"])
+                    .iter()
+                    .map(|n| self.index(n, "scene node"))
+                    .collect::<Result<_, _>>()?
+            }
+            // Without scenes, every node that is nobody's child is a root
+            _ => {
+                let nodes = array(&self.json["nodes"]);
+                let mut is_child = vec![false; nodes.len()];
+                for node in nodes {
+                    for child in array(&node["children"]) {
+                        if let Some(c) = child.as_u64() {
+                            if let Some(flag) = is_child.get_mut(c as usize) {
+                                *flag = true;
+                            }
+                        }
+                    }
+                }
+                (0..nodes.len()).filter(|&i| !is_child[i]).collect()
+            }
+        };
+
+        for node in root_nodes {
+            self.load_node(node, &Mat4::IDENTITY, 0, &mut scene)?;
+        }
+
+        Ok(scene)
+    }
+
+    fn load_node(
+        &self,
+        index: usize,
+        parent: &Mat4,
+        depth: usize,
+        scene: &mut GltfScene,
+    ) -> Result<(), GltfError> {
+        if depth > MAX_NODE_DEPTH {
+            return Err(self.error("node hierarchy is too deep or cyclic"));
+        }
+
+        let node = self.json["nodes"]
+            .get(index)
+            .ok_or_else(|| self.error(format!("node {} does not exist", index)))?;
+        let world = *parent * self.node_matrix(node)?;
+
+        if let Some(mesh) = node.get("mesh") {
+            let mesh = self.index(mesh, "node mesh")?;
+            self.load_mesh(mesh, &world, scene)?;
+        }
+
+        if let Some(camera) = node.get("camera") {
+            let camera = self.index(camera, "node camera")?;
+            if let Some(camera) = self.load_camera(camera, &world)? {
+                scene.cameras.push(camera);
+            }
+        }
+
+        for child in array(&node["children"]) {
+            let child = self.index(child, "node child")?;
+            self.load_node(child, &world, depth + 1, scene)?;
+        }
+
+        Ok(())
+    }
+
+    fn node_matrix(&self, node: &Value) -> Result<Mat4, GltfError> {
+        if let Some(matrix) = node.get("matrix") {
+            let m = self.numbers::<16>(matrix, "node matrix")?;
+            return Ok(Mat4::from_column_major(&m));
+        }
+
+        let t = match node.get("translation") {
+            Some(t) => self.numbers::<3>(t, "node translation")?,
+            None => [0.0, 0.0, 0.0],
+        };
+        let r = match node.get("rotation") {
+            Some(r) => self.numbers::<4>(r, "node rotation")?,
+            None => [0.0, 0.0, 0.0, 1.0],
+        };
+        let s = match node.get("scale") {
+            Some(s) => self.numbers::<3>(s, "node scale")?,
+            None => [1.0, 1.0, 1.0],
+        };
+
+        // Applied as T * R * S
+        Ok(Mat4::translation(&Vec3::new(t[0], t[1], t[2]))
+            * Mat4::rotation_quaternion(r)
+            * Mat4::scaling(&Vec3::new(s[0], s[1], s[2])))
+    }
+
+    fn load_camera(&self, index: usize, world: &Mat4) -> Result<Option<GltfCamera>, GltfError> {
+        let camera = self.json["cameras"]
+            .get(index)
+            .ok_or_else(|| self.error(format!("camera {} does not exist", index)))?;
+
+        // Orthographic cameras have no equivalent
+        let Some(perspective) = camera.get("perspective") else {
+            return Ok(None);
+        };
+
+        let yfov = perspective["yfov"]
+            .as_f64()
+            .ok_or_else(|| self.error(format!("camera {} has no yfov", index)))?;
+
+        // glTF cameras look down their local -z axis with +y up
+        let lookfrom = world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+        let forward = world
+            .transform_vector(&Vec3::new(0.0, 0.0, -1.0))
+            .unit_vector();
+        let vup = world
+            .transform_vector(&Vec3::new(0.0, 1.0, 0.0))
+            .unit_vector();
+
+        Ok(Some(GltfCamera {
+            name: camera["name"].as_str().map(String::from),
+            vfov: yfov.to_degrees(),
+            aspect_ratio: perspective["aspectRatio"].as_f64(),
+            lookfrom,
+            lookat: lookfrom + forward,
+            vup,
+        }))
+    }
+
+    fn load_mesh(
+        &self,
+        index: usize,
+        world: &Mat4,
+        scene: &mut GltfScene,
+    ) -> Result<(), GltfError> {
+        let mesh = self.json["meshes"]
+            .get(index)
+            .ok_or_else(|| self.error(format!("mesh {} does not exist", index)))?;
+        let name = mesh["name"].as_str().map(String::from);
+
+        // Normals transform by the inverse transpose; a singular transform flattens the mesh,
+        // which then falls back to its geometric normals
+        let normal_transform = world.inverse().map(|inv| inv.transpose());
+        let flip_winding = world.determinant3() < 0.0;
+
+        for (p, primitive) in array(&mesh["primitives"]).iter().enumerate() {
+            let context = format!("mesh {} primitive {}", index, p);
+            let attributes = &primitive["attributes"];
+
+            let Some(position_accessor) = attributes.get("POSITION") else {
+                return Err(self.error(format!("{} has no POSITION attribute", context)));
+            };
+            let positions = self.read_vec3s(self.index(position_accessor, &context)?, None)?;
+            let vertex_count = positions.len();
+
+            let mut buffers =
+                MeshBuffers::new(positions.iter().map(|p| world.transform_point(p)).collect());
+
+            if let (Some(normal_accessor), Some(normal_transform)) =
+                (attributes.get("NORMAL"), normal_transform)
+            {
+                let normals =
+                    self.read_vec3s(self.index(normal_accessor, &context)?, Some(vertex_count))?;
+                buffers.normals = normals
+                    .iter()
+                    .map(|n| normal_transform.transform_vector(n).unit_vector())
+                    .collect();
+            }
+
+            if let Some(uv_accessor) = attributes.get("TEXCOORD_0") {
+                let (values, components) =
+                    self.read_accessor(self.index(uv_accessor, &context)?, Some(vertex_count))?;
+                if components != 2 {
+                    return Err(self.error(format!("{} TEXCOORD_0 is not VEC2", context)));
+                }
+                // glTF puts the origin of texture space at the top left of the image, flip v to
+                // put it at the bottom left like the other loaders
+                buffers.uvs = values.chunks(2).map(|uv| [uv[0], 1.0 - uv[1]]).collect();
+            }
+
+            let indices: Vec<usize> = match primitive.get("indices") {
+                Some(accessor) => {
+                    let (values, _) = self.read_accessor(self.index(accessor, &context)?, None)?;
+                    values.iter().map(|&i| i as usize).collect()
+                }
+                None => (0..vertex_count).collect(),
+            };
+            if let Some(&i) = indices.iter().find(|&&i| i >= vertex_count) {
+                return Err(self.error(format!(
+                    "{} index {} out of range ({} vertices)",
+                    context, i, vertex_count
+                )));
+            }
+
+            let mode = primitive["mode"].as_u64().unwrap_or(4);
+            let mut triangles: Vec<[usize; 3]> = match mode {
+                4 => indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect(),
+                5 => (2..indices.len())
+                    .map(|i| {
+                        // Every other triangle of a strip is wound the other way
+                        if i % 2 == 0 {
+                            [indices[i - 2], indices[i - 1], indices[i]]
+                        } else {
+                            [indices[i - 1], indices[i - 2], indices[i]]
+                        }
+                    })
+                    .collect(),
+                6 => (2..indices.len())
+                    .map(|i| [indices[0], indices[i - 1], indices[i]])
+                    .collect(),
+                // Points and lines are not rendered
+                _ => continue,
+            };
+
+            if flip_winding {
+                for t in &mut triangles {
+                    t.swap(1, 2);
+                }
+            }
+
+            let material_index = match primitive.get("material") {
+                Some(m) => Some(self.index(m, &context)?),
+                None => None,
+            };
+            let mat = match material_index {
+                Some(m) => scene
+                    .materials
+                    .get(m)
+                    .ok_or_else(|| {
+                        self.error(format!("{} material {} does not exist", context, m))
+                    })?
+                    .material
+                    .clone(),
+                None => self.parse_material(&Value::Null).material,
+            };
+
+            scene.meshes.push(GltfMesh {
+                name: name.clone(),
+                material: material_index,
+                mesh: TriangleMesh::try_new(Arc::new(buffers), triangles, mat)
+                    .map_err(|message| self.error(format!("{} {}", context, message)))?,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn load_materials(&self, images: &[GltfImage]) -> Result<Vec<GltfMaterial>, GltfError> {
+        let materials = array(&self.json["materials"]);
+        let mut result = Vec::with_capacity(materials.len());
+        for material in materials {
+            let mut parsed = self.parse_material(material);
+            parsed.base_color_texture =
+                self.texture_image(&material["pbrMetallicRoughness"]["baseColorTexture"])?;
+            parsed.metallic_roughness_texture =
+                self.texture_image(&material["pbrMetallicRoughness"]["metallicRoughnessTexture"])?;
+            parsed.material = parsed.to_material(images);
+            result.push(parsed);
+        }
+        Ok(result)
+    }
+
+    // Parse a material, with the defaults from the glTF specification for missing values
+    fn parse_material(&self, material: &Value) -> GltfMaterial {
+        let pbr = &material["pbrMetallicRoughness"];
+        let base_color = array(&pbr["baseColorFactor"])
+            .iter()
+            .map(|v| v.as_f64().unwrap_or(1.0))
+            .collect::<Vec<_>>();
+        let (base_color, alpha) = match base_color.as_slice() {
+            [r, g, b, a] => (Color::new(*r, *g, *b), *a),
+            _ => (Color::new(1.0, 1.0, 1.0), 1.0),
+        };
+        let alpha = if material["alphaMode"].as_str() == Some("BLEND") {
+            alpha
+        } else {
+            1.0
+        };
+
+        let extensions = &material["extensions"];
+        let mut parsed = GltfMaterial {
+            name: material["name"].as_str().map(String::from),
+            base_color,
+            alpha,
+            metallic: pbr["metallicFactor"].as_f64().unwrap_or(1.0),
+            roughness: pbr["roughnessFactor"].as_f64().unwrap_or(1.0),
+            transmission: extensions["KHR_materials_transmission"]["transmissionFactor"]
+                .as_f64()
+                .unwrap_or(0.0),
+            ior: extensions["KHR_materials_ior"]["ior"]
+                .as_f64()
+                .unwrap_or(1.5),
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            material: Material::lambertian(base_color),
+        };
+        parsed.material = parsed.to_material(&[]);
+        parsed
+    }
+
+    // Resolve a textureInfo object to the index of its source image
+    fn texture_image(&self, texture_info: &Value) -> Result<Option<usize>, GltfError> {
+        let Some(texture) = texture_info.get("index") else {
+            return Ok(None);
+        };
+        let texture = self.index(texture, "texture")?;
+        let source = self.json["textures"]
+            .get(texture)
+            .ok_or_else(|| self.error(format!("texture {} does not exist", texture)))?
+            .get("source");
+
+        source.map(|s| self.index(s, "texture source")).transpose()
+    }
+
+    fn load_images(&self) -> Result<Vec<GltfImage>, GltfError> {
+        array(&self.json["images"])
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                let data = if let Some(uri) = image["uri"].as_str() {
+                    self.read_uri(uri)?
+                } else if let Some(view) = image.get("bufferView") {
+                    let view = self.index(view, "image bufferView")?;
+                    let (bytes, _) = self.buffer_view(view)?;
+                    bytes.to_vec()
+                } else {
+                    return Err(self.error(format!("image {} has no uri or bufferView", index)));
+                };
+
+                Ok(GltfImage {
+                    name: image["name"].as_str().map(String::from),
+                    mime_type: image["mimeType"].as_str().map(String::from),
+                    data,
+                })
+            })
+            .collect()
+    }
+
+    // Returns the bytes of a buffer view and its byte stride, if any
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let view = self.json["bufferViews"]
+            .get(index)
+            .ok_or_else(|| self.error(format!("bufferView {} does not exist", index)))?;
+
+        let buffer_index = self.index(&view["buffer"], "bufferView buffer")?;
+        let buffer = self
+            .buffers
+            .get(buffer_index)
+            .ok_or_else(|| self.error(format!("buffer {} does not exist", buffer_index)))?;
+        let offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let length = view["byteLength"].as_u64().unwrap_or(0) as usize;
+
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| self.error(format!("bufferView {} exceeds its buffer", index)))?;
+        let stride = view["byteStride"].as_u64().map(|s| s as usize);
+        Ok((bytes, stride))
+    }
+
+    // Read all elements of an accessor as f64 values, returning them along with the number of
+    // components per element. Vertex attributes pass the vertex count their accessor must match.
+    fn read_accessor(
+        &self,
+        index: usize,
+        expected_count: Option<usize>,
+    ) -> Result<(Vec<f64>, usize), GltfError> {
+        let accessor = self.json["accessors"]
+            .get(index)
+            .ok_or_else(|| self.error(format!("accessor {} does not exist", index)))?;
+
+        if accessor.get("sparse").is_some() {
+            return Err(self.error(format!(
+                "accessor {} is sparse, which is not supported",
+                index
+            )));
+        }
+
+        let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+        if let Some(expected) = expected_count.filter(|&expected| expected != count) {
+            return Err(self.error(format!(
+                "accessor {} has {} elements, expected {}",
+                index, count, expected
+            )));
+        }
+        let components = match accessor["type"].as_str().unwrap_or("") {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            other => {
+                return Err(self.error(format!("accessor {} has unknown type '{}'", index, other)))
+            }
+        };
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => {
+                return Err(self.error(format!(
+                    "accessor {} has unknown componentType {}",
+                    index, component_type
+                )))
+            }
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+
+        // The count, offset and stride are untrusted, so check the arithmetic on them and that
+        // the elements fit in the buffer view before allocating anything
+        let value_count = count
+            .checked_mul(components)
+            .ok_or_else(|| self.error(format!("accessor {} has too many elements", index)))?;
+
+        // Accessors without a buffer view are all zeros. Nothing in the file bounds their
+        // count, so only accept them where another accessor has already fixed it.
+        let Some(view) = accessor.get("bufferView") else {
+            if expected_count.is_none() {
+                return Err(self.error(format!("accessor {} has no bufferView", index)));
+            }
+            return Ok((vec![0.0; value_count], components));
+        };
+        let (bytes, stride) = self.buffer_view(self.index(view, "accessor bufferView")?)?;
+        let element_size = components * component_size;
+        let stride = stride.unwrap_or(element_size);
+        if stride < element_size {
+            return Err(self.error(format!(
+                "accessor {} has a byteStride of {}, smaller than its {} byte elements",
+                index, stride, element_size
+            )));
+        }
+        let offset = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(offset))
+                .and_then(|start| start.checked_add(element_size)),
+            None => Some(offset),
+        };
+        if !matches!(end, Some(end) if end <= bytes.len()) {
+            return Err(self.error(format!("accessor {} exceeds its bufferView", index)));
+        }
+
+        let mut values = Vec::with_capacity(value_count);
+        for i in 0..count {
+            for c in 0..components {
+                let at = offset + i * stride + c * component_size;
+                let b = &bytes[at..at + component_size];
+                let value = match component_type {
+                    5120 => {
+                        let v = b[0] as i8 as f64;
+                        if normalized {
+                            (v / 127.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    5121 => {
+                        let v = b[0] as f64;
+                        if normalized {
+                            v / 255.0
+                        } else {
+                            v
+                        }
+                    }
+                    5122 => {
+                        let v = i16::from_le_bytes([b[0], b[1]]) as f64;
+                        if normalized {
+                            (v / 32767.0).max(-1.0)
+                        } else {
+                            v
+                        }
+                    }
+                    5123 => {
+                        let v = u16::from_le_bytes([b[0], b[1]]) as f64;
+                        if normalized {
+                            v / 65535.0
+                        } else {
+                            v
+                        }
+                    }
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(value);
+            }
+        }
+
+        Ok((values, components))
+    }
+
+    fn read_vec3s(
+        &self,
+        accessor: usize,
+        expected_count: Option<usize>,
+    ) -> Result<Vec<Vec3>, GltfError> {
+        let (values, components) = self.read_accessor(accessor, expected_count)?;
+        if components != 3 {
+            return Err(self.error(format!("accessor {} is not VEC3", accessor)));
+        }
+        Ok(values
+            .chunks(3)
+            .map(|v| Vec3::new(v[0], v[1], v[2]))
+            .collect())
+    }
+
+    fn index(&self, value: &Value, what: &str) -> Result<usize, GltfError> {
+        value
+            .as_u64()
+            .map(|i| i as usize)
+            .ok_or_else(|| self.error(format!("invalid {} index {}", what, value)))
+    }
+
+    fn numbers<const N: usize>(&self, value: &Value, what: &str) -> Result<[f64; N], GltfError> {
+        let values = array(value);
+        if values.len() != N {
+            return Err(self.error(format!("{} must have {} elements", what, N)));
+        }
+
+        let mut result = [0.0; N];
+        for (r, v) in result.iter_mut().zip(values) {
+            *r = v
+                .as_f64()
+                .ok_or_else(|| self.error(format!("{} must contain numbers", what)))?;
+        }
+        Ok(result)
+    }
+}
+
+fn array(value: &Value) -> &[Value] {
+    value.as_array().map_or(&[], |a| a.as_slice())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None,
+        }
+    }
+
+    let input: Vec<u8> = input
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+        .collect();
+
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    for chunk in input.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+
+        let mut bits = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            bits |= value(c)? << (18 - 6 * i);
+        }
+
+        output.push((bits >> 16) as u8);
+        if chunk.len() > 2 {
+            output.push((bits >> 8) as u8);
+        }
+        if chunk.len() > 3 {
+            output.push(bits as u8);
+        }
+    }
+
+    Some(output)
+}
+
+// Decode %XX escapes in a relative URI
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(data: &[u8]) -> Result<GltfScene, GltfError> {
+        GltfLoader::new(Path::new("test.gltf"), data)?.load_scene()
+    }
+
+    fn encode_base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut output = String::new();
+        for chunk in data.chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    output.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    output.push('=');
+                }
+            }
+        }
+        output
+    }
+
+    // One triangle with positions and normals interleaved in a single strided buffer view
+    fn interleaved_triangle() -> Vec<u8> {
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let mut data = Vec::new();
+        for p in positions {
+            for value in p.iter().chain(&[0.0, 0.0, 1.0]) {
+                data.extend_from_slice(&(*value as f32).to_le_bytes());
+            }
+        }
+        data
+    }
+
+    fn triangle_json(buffer: &str, accessors: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": [{{"mesh": 0}}],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0, "NORMAL": 1}}}}]}}],
+                "buffers": [{}],
+                "bufferViews": [{{"buffer": 0, "byteLength": 72, "byteStride": 24}}],
+                "accessors": {}
+            }}"#,
+            buffer, accessors
+        )
+    }
+
+    const INTERLEAVED_ACCESSORS: &str = r#"[
+        {"bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3},
+        {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "type": "VEC3", "count": 3}
+    ]"#;
+
+    fn assert_interleaved_triangle(scene: &GltfScene) {
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0].mesh;
+        assert_eq!(mesh.triangle_count(), 1);
+
+        let buffers = mesh.buffers();
+        let expected = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        assert_eq!(buffers.positions.len(), 3);
+        for (p, e) in buffers.positions.iter().zip(expected) {
+            assert!((*p - e).length() < 1e-12);
+        }
+        for n in &buffers.normals {
+            assert!((*n - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn reads_strided_accessors_from_a_data_uri() {
+        let buffer = format!(
+            r#"{{"byteLength": 72, "uri": "data:application/octet-stream;base64,{}"}}"#,
+            encode_base64(&interleaved_triangle())
+        );
+        let json = triangle_json(&buffer, INTERLEAVED_ACCESSORS);
+
+        assert_interleaved_triangle(&load(json.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn reads_glb_chunks() {
+        let mut json = triangle_json(r#"{"byteLength": 72}"#, INTERLEAVED_ACCESSORS).into_bytes();
+        // Chunks are padded to four bytes, the JSON one with spaces
+        json.resize(json.len() + (4 - json.len() % 4) % 4, b' ');
+        let bin = interleaved_triangle();
+
+        let mut glb = Vec::new();
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        for value in [GLB_MAGIC, 2, length as u32] {
+            glb.extend_from_slice(&value.to_le_bytes());
+        }
+        for (chunk_type, chunk) in [(GLB_CHUNK_JSON, &json), (GLB_CHUNK_BIN, &bin)] {
+            glb.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&chunk_type.to_le_bytes());
+            glb.extend_from_slice(chunk);
+        }
+        assert_interleaved_triangle(&load(&glb).unwrap());
+
+        // A chunk running past the declared length is an error, not a panic
+        let mut truncated = glb.clone();
+        truncated[8..12].copy_from_slice(&((length - 4) as u32).to_le_bytes());
+        assert!(load(&truncated).is_err());
+    }
+
+    #[test]
+    fn rejects_accessors_outside_their_buffer_view() {
+        let buffer = format!(
+            r#"{{"byteLength": 72, "uri": "data:;base64,{}"}}"#,
+            encode_base64(&interleaved_triangle())
+        );
+        let bad_accessors = [
+            // Last element runs past the end of the view
+            r#"[{"bufferView": 0, "byteOffset": 16, "componentType": 5126, "type": "VEC3", "count": 3},
+                {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "type": "VEC3", "count": 3}]"#,
+            // Offset and count that overflow
+            r#"[{"bufferView": 0, "byteOffset": 18446744073709551600, "componentType": 5126, "type": "VEC3", "count": 3},
+                {"bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 18446744073709551615}]"#,
+            // Normal count that differs from the position count
+            r#"[{"bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3},
+                {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "type": "VEC3", "count": 2}]"#,
+        ];
+        for accessors in bad_accessors {
+            assert!(load(triangle_json(&buffer, accessors).as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn bounds_accessors_without_a_buffer_view() {
+        let buffer = format!(
+            r#"{{"byteLength": 72, "uri": "data:;base64,{}"}}"#,
+            encode_base64(&interleaved_triangle())
+        );
+
+        // Zero normals are fine when their count matches the positions
+        let zeros = r#"[{"bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3},
+            {"componentType": 5126, "type": "VEC3", "count": 3}]"#;
+        let scene = load(triangle_json(&buffer, zeros).as_bytes()).unwrap();
+        assert_eq!(scene.meshes[0].mesh.buffers().positions.len(), 3);
+
+        // A huge count must be rejected before anything is allocated for it
+        let huge = r#"[{"bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3},
+            {"componentType": 5126, "type": "VEC3", "count": 1000000000000}]"#;
+        assert!(load(triangle_json(&buffer, huge).as_bytes()).is_err());
+
+        // Positions have no other accessor to bound them
+        let positions = r#"[{"componentType": 5126, "type": "VEC3", "count": 1000000000000},
+            {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "type": "VEC3", "count": 3}]"#;
+        assert!(load(triangle_json(&buffer, positions).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs bG8").unwrap(), b"hello");
+        // URL-safe alphabet
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(decode_base64("aGVsb").is_none());
+        assert!(decode_base64("a*==").is_none());
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(
+            percent_decode("my%20model%2Fparts.bin"),
+            "my model/parts.bin"
+        );
+        assert_eq!(percent_decode("caf%C3%A9.bin"), "café.bin");
+        // Incomplete or invalid escapes are kept as they are
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("a%zz%4"), "a%zz%4");
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    texture::parse_netpbm,
+    triangle::intersect_triangle,
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum HeightfieldError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for HeightfieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeightfieldError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            HeightfieldError::Format { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl Error for HeightfieldError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HeightfieldError::Io { source, .. } => Some(source),
+            HeightfieldError::Format { .. } => None,
+        }
+    }
+}
+
+// Grid of elevation samples, stored row by row. Samples are spaced evenly along x within a row
+// and along z from one row to the next.
+#[derive(Debug, Clone)]
+pub struct HeightGrid {
+    width: usize,
+    depth: usize,
+    heights: Vec<f64>,
+}
+
+impl HeightGrid {
+    pub fn new(width: usize, depth: usize, heights: Vec<f64>) -> Self {
+        assert!(
+            width >= 2 && depth >= 2,
+            "height grid needs at least 2x2 samples"
+        );
+        assert_eq!(
+            heights.len(),
+            width * depth,
+            "height count doesn't match grid size"
+        );
+
+        HeightGrid {
+            width,
+            depth,
+            heights,
+        }
+    }
+
+    // Load a greyscale PGM image (plain P2 or binary P5), with heights scaled to [0, 1]. Image
+    // rows become rows of the grid.
+    pub fn load_pgm<P: AsRef<Path>>(path: P) -> Result<Self, HeightfieldError> {
+        let path = path.as_ref();
+        let data = read_file(path)?;
+        parse_pgm(&data).map_err(|message| HeightfieldError::Format {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    // Load raw little endian 32-bit floats, width samples per row, as heights in world units
+    pub fn load_raw<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        depth: usize,
+    ) -> Result<Self, HeightfieldError> {
+        let path = path.as_ref();
+        let data = read_file(path)?;
+
+        let format_error = |message| HeightfieldError::Format {
+            path: path.to_path_buf(),
+            message,
+        };
+        if width < 2 || depth < 2 {
+            return Err(format_error(format!(
+                "grid of {}x{} samples is too small",
+                width, depth
+            )));
+        }
+
+        // The size comes from the caller, so guard against it overflowing
+        let expected = width
+            .checked_mul(depth)
+            .and_then(|samples| samples.checked_mul(4))
+            .ok_or_else(|| {
+                format_error(format!("grid of {}x{} samples is too large", width, depth))
+            })?;
+        if data.len() != expected {
+            return Err(format_error(format!(
+                "expected {} bytes for a {}x{} grid, found {}",
+                expected,
+                width,
+                depth,
+                data.len()
+            )));
+        }
+
+        let heights = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        Ok(HeightGrid::new(width, depth, heights))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Bilinearly interpolated height at texture coordinates (u, v) in [0, 1], with u across
+    // each row and v from the first row to the last
+    pub fn sample(&self, u: f64, v: f64) -> f64 {
+        let x = u.clamp(0.0, 1.0) * (self.width - 1) as f64;
+        let y = v.clamp(0.0, 1.0) * (self.depth - 1) as f64;
+        let i = (x as usize).min(self.width - 2);
+        let j = (y as usize).min(self.depth - 2);
+        let (fx, fy) = (x - i as f64, y - j as f64);
+
+        let top = (1.0 - fx) * self.height(i, j) + fx * self.height(i + 1, j);
+        let bottom = (1.0 - fx) * self.height(i, j + 1) + fx * self.height(i + 1, j + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.width + i]
+    }
+}
+
+// Terrain surface over a height grid, intersected by walking the ray through the grid cells
+// instead of meshing them. Each cell is split into two triangles and shaded with normals
+// interpolated from the grid's slopes. The grid spans size.x by size.z from corner, and a
+// height h sits at corner.y + h * size.y. UVs run from 0 to 1 across the grid along x and z.
+pub struct Heightfield {
+    grid: HeightGrid,
+    cell_ranges: Vec<Interval>, // height range of each cell, to skip cells the ray passes over
+    corner: Point3,
+    size: Vec3,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Heightfield {
+    pub fn new(grid: HeightGrid, corner: Point3, size: Vec3, mat: Material) -> Self {
+        let mut heightfield = Heightfield {
+            cell_ranges: Vec::with_capacity((grid.width - 1) * (grid.depth - 1)),
+            grid,
+            corner,
+            size,
+            mat,
+            bbox: Aabb::EMPTY,
+        };
+
+        let mut y_range = Interval::EMPTY;
+        for j in 0..heightfield.grid.depth - 1 {
+            for i in 0..heightfield.grid.width - 1 {
+                let range = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)]
+                    .iter()
+                    .map(|&(i, j)| heightfield.vertex(i, j).y())
+                    .fold(Interval::EMPTY, |range, y| {
+                        Interval::enclosing(&range, &Interval::new(y, y))
+                    });
+                y_range = Interval::enclosing(&y_range, &range);
+                heightfield.cell_ranges.push(range);
+            }
+        }
+
+        heightfield.bbox = Aabb::new(
+            Interval::enclosing(
+                &Interval::new(corner.x(), corner.x()),
+                &Interval::new(corner.x() + size.x(), corner.x() + size.x()),
+            ),
+            y_range,
+            Interval::enclosing(
+                &Interval::new(corner.z(), corner.z()),
+                &Interval::new(corner.z() + size.z(), corner.z() + size.z()),
+            ),
+        );
+        heightfield
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.size.x() / (self.grid.width - 1) as f64,
+            self.size.z() / (self.grid.depth - 1) as f64,
+        )
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        let (dx, dz) = self.cell_size();
+        self.corner
+            + Vec3::new(
+                i as f64 * dx,
+                self.grid.height(i, j) * self.size.y(),
+                j as f64 * dz,
+            )
+    }
+
+    // Smooth vertex normal from the central difference slopes of the grid
+    fn vertex_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.grid.width - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.grid.depth - 1));
+
+        let slope_x = (self.vertex(i1, j).y() - self.vertex(i0, j).y()) / ((i1 - i0) as f64 * dx);
+        let slope_z = (self.vertex(i, j1).y() - self.vertex(i, j0).y()) / ((j1 - j0) as f64 * dz);
+        Vec3::new(-slope_x, 1.0, -slope_z).unit_vector()
+    }
+
+    fn hit_cell(&self, i: usize, j: usize, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest_so_far = ray_t.max;
+        let mut hit = None;
+
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            let indices = triangle.map(|k| corners[k]);
+            let [p0, p1, p2] = indices.map(|(i, j)| self.vertex(i, j));
+
+            if let Some((t, b)) =
+                intersect_triangle(r, Interval::new(ray_t.min, closest_so_far), p0, p1, p2)
+            {
+                closest_so_far = t;
+                hit = Some((t, b, indices, (p1 - p0).cross(&(p2 - p0))));
+            }
+        }
+
+        let (t, b, indices, n) = hit?;
+
+        // The terrain's outward side is up
+        let outward_normal = if n.y() < 0.0 { -n } else { n }.unit_vector();
+        let mut rec = HitRecord::new(r.at(t), t, r, &outward_normal, &self.mat);
+        let shading_normal = indices
+            .iter()
+            .zip(b)
+            .fold(Vec3::new(0.0, 0.0, 0.0), |n, (&(i, j), w)| {
+                n + w * self.vertex_normal(i, j)
+            });
+        rec.set_shading_normal(&shading_normal.unit_vector());
+
+        rec.u = ((rec.p.x() - self.corner.x()) / self.size.x()).clamp(0.0, 1.0);
+        rec.v = ((rec.p.z() - self.corner.z()) / self.size.z()).clamp(0.0, 1.0);
+        Some(rec)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let clipped = self.bbox.clip(r, ray_t)?;
+
+        // Walk the cells under the ray in grid coordinates, where cells are unit squares
+        let (dx, dz) = self.cell_size();
+        let cells = [self.grid.width - 1, self.grid.depth - 1];
+        let origin = [
+            (r.origin().x() - self.corner.x()) / dx,
+            (r.origin().z() - self.corner.z()) / dz,
+        ];
+        let dir = [r.direction().x() / dx, r.direction().z() / dz];
+
+        let mut cell = [0; 2];
+        let mut step = [0isize; 2];
+        let mut t_next = [f64::INFINITY; 2];
+        let mut t_delta = [f64::INFINITY; 2];
+        for axis in 0..2 {
+            let start = origin[axis] + clipped.min * dir[axis];
+            cell[axis] = (start.floor().max(0.0) as usize).min(cells[axis] - 1);
+
+            if dir[axis] > 0.0 {
+                step[axis] = 1;
+                t_next[axis] = ((cell[axis] + 1) as f64 - origin[axis]) / dir[axis];
+                t_delta[axis] = 1.0 / dir[axis];
+            } else if dir[axis] < 0.0 {
+                step[axis] = -1;
+                t_next[axis] = (cell[axis] as f64 - origin[axis]) / dir[axis];
+                t_delta[axis] = -1.0 / dir[axis];
+            }
+        }
+
+        let mut t_enter = clipped.min;
+        loop {
+            let t_exit = t_next[0].min(t_next[1]).min(clipped.max);
+
+            // Only test the cell's triangles if the ray's height range over the cell overlaps
+            // the cell's height range
+            let y0 = r.at(t_enter).y();
+            let y1 = r.at(t_exit).y();
+            let range = self.cell_ranges[cell[1] * cells[0] + cell[0]];
+            if y0.min(y1) <= range.max && y0.max(y1) >= range.min {
+                if let Some(rec) = self.hit_cell(cell[0], cell[1], r, clipped) {
+                    return Some(rec);
+                }
+            }
+
+            if t_exit >= clipped.max {
+                return None;
+            }
+
+            let axis = if t_next[0] < t_next[1] { 0 } else { 1 };
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= cells[axis] as isize {
+                return None;
+            }
+            cell[axis] = next as usize;
+            t_enter = t_next[axis];
+            t_next[axis] += t_delta[axis];
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, HeightfieldError> {
+    fs::read(path).map_err(|source| HeightfieldError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn parse_pgm(data: &[u8]) -> Result<HeightGrid, String> {
+    let image = parse_netpbm(data)?;
+    if image.channels != 1 {
+        return Err("expected a greyscale P2 or P5 image".to_string());
+    }
+    if image.width < 2 || image.height < 2 {
+        return Err(format!(
+            "image of {}x{} pixels is too small",
+            image.width, image.height
+        ));
+    }
+
+    Ok(HeightGrid::new(image.width, image.height, image.values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    // Bumpy 5x4 grid with distinct heights in every cell
+    fn bumpy_grid() -> HeightGrid {
+        let heights = (0..20).map(|k| ((k * 7) % 11) as f64 / 10.0).collect();
+        HeightGrid::new(5, 4, heights)
+    }
+
+    #[test]
+    fn sample_interpolates_between_grid_points() {
+        let grid = HeightGrid::new(2, 2, vec![0.0, 1.0, 2.0, 3.0]);
+
+        assert_eq!(grid.sample(0.0, 0.0), 0.0);
+        assert_eq!(grid.sample(1.0, 0.0), 1.0);
+        assert_eq!(grid.sample(0.0, 1.0), 2.0);
+        assert_eq!(grid.sample(1.0, 1.0), 3.0);
+        assert!((grid.sample(0.5, 0.5) - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn grid_walk_matches_testing_every_cell() {
+        let grid = bumpy_grid();
+        let corner = Point3::new(-1.0, 0.5, -2.0);
+        let size = Vec3::new(4.0, 2.0, 3.0);
+        let heightfield = Heightfield::new(grid, corner, size, material());
+
+        let mut hits = 0;
+        for k in 0..400 {
+            let a = k as f64 * 0.37;
+            let origin = Point3::new(3.0 * a.cos(), 4.0, 3.0 * (1.3 * a).sin());
+            let target = Point3::new(
+                -1.5 + 5.0 * ((k * 13) % 17) as f64 / 16.0,
+                1.0,
+                -2.5 + 4.0 * ((k * 5) % 19) as f64 / 18.0,
+            );
+            let r = Ray::new(origin, target - origin);
+            let ray_t = Interval::new(0.0, f64::INFINITY);
+
+            let expected = (0..heightfield.grid.depth - 1)
+                .flat_map(|j| (0..heightfield.grid.width - 1).map(move |i| (i, j)))
+                .filter_map(|(i, j)| heightfield.hit_cell(i, j, &r, ray_t))
+                .map(|rec| rec.t)
+                .fold(f64::INFINITY, f64::min);
+
+            match heightfield.hit(&r, ray_t) {
+                Some(rec) => {
+                    hits += 1;
+                    assert!((rec.t - expected).abs() < 1e-9, "ray {}", k);
+                }
+                None => assert!(expected.is_infinite(), "ray {} missed a cell", k),
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn flat_grid_hit_has_up_normal_and_uv() {
+        let grid = HeightGrid::new(3, 3, vec![0.5; 9]);
+        let heightfield = Heightfield::new(
+            grid,
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 2.0, 4.0),
+            material(),
+        );
+
+        let r = Ray::new(Point3::new(0.5, 3.0, 3.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = heightfield
+            .hit(&r, Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12);
+        assert!((rec.v - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn loads_pgm_and_raw_files() {
+        let dir =
+            std::env::temp_dir().join(format!("raytracer-heightfield-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let pgm = dir.join("terrain.pgm");
+        fs::write(&pgm, "P2\n# terrain\n3 2\n4\n0 1 2\n3 4 2\n").unwrap();
+        let grid = HeightGrid::load_pgm(&pgm).unwrap();
+        assert_eq!((grid.width(), grid.depth()), (3, 2));
+        assert_eq!(grid.heights, vec![0.0, 0.25, 0.5, 0.75, 1.0, 0.5]);
+
+        let raw = dir.join("terrain.raw");
+        let heights = [0.5f32, -1.0, 2.0, 8.0, 0.0, 3.5];
+        let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+        fs::write(&raw, bytes).unwrap();
+        let grid = HeightGrid::load_raw(&raw, 2, 3).unwrap();
+        assert_eq!((grid.width(), grid.depth()), (2, 3));
+        assert_eq!(grid.heights, vec![0.5, -1.0, 2.0, 8.0, 0.0, 3.5]);
+
+        let error = |result: Result<HeightGrid, HeightfieldError>| {
+            result.expect_err("expected an error").to_string()
+        };
+        assert!(error(HeightGrid::load_raw(&raw, 3, 3)).contains("expected 36 bytes"));
+        assert!(error(HeightGrid::load_raw(&raw, 1, 6)).contains("too small"));
+        assert!(error(HeightGrid::load_raw(&raw, usize::MAX, 2)).contains("too large"));
+
+        fs::write(&pgm, "P2\n1 4\n255\n0 1 2 3\n").unwrap();
+        assert!(error(HeightGrid::load_pgm(&pgm)).contains("too small"));
+        fs::write(&pgm, "P3\n2 2\n255\n0 0 0 0 0 0 0 0 0 0 0 0\n").unwrap();
+        assert!(error(HeightGrid::load_pgm(&pgm)).contains("greyscale"));
+        assert!(matches!(
+            HeightGrid::load_pgm(dir.join("missing.pgm")),
+            Err(HeightfieldError::Io { .. })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,18 +1,36 @@
+use std::borrow::Cow;
+
+use crate::aabb::Aabb;
+use crate::color::Color;
 use crate::interval::Interval;
+use crate::light::Light;
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::{Point3, Vec3};
 
-pub struct HitRecord {
+// Limits the number of surface crossings collected by Hittable::hit_intervals
+const MAX_CROSSINGS: usize = 64;
+
+// Relative step taken past each crossing when searching for the next one
+const CROSSING_EPSILON: f64 = 1e-9;
+
+// Intersection found by a hit query. The material is borrowed from the object that was hit,
+// except for media that make up a phase function per scattering event.
+#[derive(Clone)]
+pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
-    pub mat: Material,
+    pub mat: Cow<'a, Material>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
+    pub tangent: Vec3, // direction along the surface for anisotropic shading, zero if undefined
+    pub color: Color,  // vertex color interpolated across a mesh face, white elsewhere
 }
 
-impl HitRecord {
-    pub fn new(p: Point3, t: f64, r: &Ray, outward_normal: &Vec3, mat: Material) -> Self {
+impl<'a> HitRecord<'a> {
+    pub fn new(p: Point3, t: f64, r: &Ray, outward_normal: &Vec3, mat: &'a Material) -> Self {
         // NOTE: the param outward_normal is assumed to have unit length
         let front_face = r.direction().dot(outward_normal) < 0.0;
         let normal = if front_face {
@@ -24,13 +42,78 @@ impl HitRecord {
         HitRecord {
             p,
             normal,
-            mat,
+            mat: Cow::Borrowed(mat),
             t,
+            u: 0.0,
+            v: 0.0,
             front_face,
+            tangent: Vec3::new(0.0, 0.0, 0.0),
+            color: Color::new(1.0, 1.0, 1.0),
         }
     }
+
+    // Replace the shading normal, e.g. with one interpolated across a mesh face. The geometric
+    // normal passed to new() still decides front_face; the shading normal is flipped to lie on
+    // the same side of the surface.
+    pub fn set_shading_normal(&mut self, shading_normal: &Vec3) {
+        self.normal = if shading_normal.dot(&self.normal) < 0.0 {
+            -*shading_normal
+        } else {
+            *shading_normal
+        };
+    }
 }
 
 pub trait Hittable {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>>;
+
+    fn bounding_box(&self) -> Aabb;
+
+    // Returns every span of the infinite line through the ray that lies inside the object, as
+    // (entry, exit) hit records sorted by t. Entries have front_face set and exits don't. Only
+    // meaningful for closed objects.
+    //
+    // The default implementation walks along the line collecting every surface crossing with
+    // repeated hit() calls; objects that can do better should override it.
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        let mut intervals = Vec::new();
+        let mut entry: Option<HitRecord> = None;
+        let mut t_min = f64::NEG_INFINITY;
+
+        for _ in 0..MAX_CROSSINGS {
+            let Some(rec) = self.hit(r, Interval::new(t_min, f64::INFINITY)) else {
+                break;
+            };
+            t_min = rec.t + CROSSING_EPSILON * rec.t.abs().max(1.0);
+
+            match entry.take() {
+                None if rec.front_face => entry = Some(rec),
+                // Exit without an entry, from a grazing hit or an open surface
+                None => {}
+                Some(enter) if !rec.front_face => intervals.push((enter, rec)),
+                // Two entries in a row, keep the first one
+                Some(enter) => entry = Some(enter),
+            }
+        }
+
+        intervals
+    }
+
+    // Closest hit for a shadow ray. Participating media, whose hit() picks a random scattering
+    // point, are skipped here and accounted for by transmittance() instead; everything else
+    // reports the same hit as hit().
+    fn shadow_hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.hit(r, ray_t)
+    }
+
+    // Fraction of light passing through the object's participating media along the ray over
+    // the interval. Surfaces let everything through, since shadow_hit() reports them.
+    fn transmittance(&self, _r: &Ray, _ray_t: Interval) -> f64 {
+        1.0
+    }
+
+    // Add the parts of the object that can be sampled as lights. Only spheres and quads with
+    // an emissive material are lights; containers pass the call on to what they hold, but
+    // emitters inside instances, meshes or other shapes are left to be found by scattering.
+    fn collect_lights<'a>(&'a self, _lights: &mut Vec<&'a dyn Light>) {}
 }
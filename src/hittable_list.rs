@@ -1,36 +1,46 @@
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     interval::Interval,
+    light::Light,
     ray::Ray,
 };
 
 pub struct HittableList {
     pub objects: Vec<Box<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
             objects: Vec::new(),
+            bbox: Aabb::EMPTY,
         }
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::EMPTY;
     }
 
     pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
-}
 
-impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    // Closest of the hits the query finds on each object
+    fn closest_hit<'a, F>(&'a self, r: &Ray, ray_t: Interval, query: F) -> Option<HitRecord<'a>>
+    where
+        F: Fn(&'a dyn Hittable, &Ray, Interval) -> Option<HitRecord<'a>>,
+    {
         let mut closest_hit: Option<HitRecord> = None;
         let mut closest_so_far = ray_t.max;
 
         for object in &self.objects {
-            if let Some(hit_rec) = object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+            if let Some(hit_rec) =
+                query(object.as_ref(), r, Interval::new(ray_t.min, closest_so_far))
+            {
                 closest_so_far = hit_rec.t;
                 closest_hit = Some(hit_rec);
             }
@@ -39,3 +49,36 @@ impl Hittable for HittableList {
         closest_hit
     }
 }
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.closest_hit(r, ray_t, |object, r, ray_t| object.hit(r, ray_t))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.closest_hit(r, ray_t, |object, r, ray_t| object.shadow_hit(r, ray_t))
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        self.objects
+            .iter()
+            .map(|object| object.transmittance(r, ray_t))
+            .product()
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Light>) {
+        for object in &self.objects {
+            object.collect_lights(lights);
+        }
+    }
+}
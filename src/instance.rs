@@ -0,0 +1,349 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    ray::Ray,
+    transform::{Keyframe, Transform},
+    vec3::Vec3,
+};
+
+// Number of points in time sampled per keyframe segment when bounding an animated instance
+const MOTION_BOUNDS_STEPS: usize = 32;
+
+// Places a shared object in the scene with an affine transform. Many instances can refer to
+// the same object, e.g. one mesh placed thousands of times, without copying its geometry.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = transform.bounding_box(&object.bounding_box());
+        Instance {
+            object,
+            transform,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        hit_transformed(
+            self.object.as_ref(),
+            &self.transform,
+            r,
+            ray_t,
+            |object, r, ray_t| object.hit(r, ray_t),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        intervals_transformed(self.object.as_ref(), &self.transform, r)
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        hit_transformed(
+            self.object.as_ref(),
+            &self.transform,
+            r,
+            ray_t,
+            |object, r, ray_t| object.shadow_hit(r, ray_t),
+        )
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        // t is the same in both spaces, so the interval carries over
+        let object_r = self.transform.inverse().ray(r);
+        self.object.transmittance(&object_r, ray_t)
+    }
+}
+
+// Instance whose transform is keyframed over time. Each ray sees the object as placed at the
+// ray's time; before the first and after the last keyframe the object holds still.
+pub struct AnimatedInstance {
+    object: Arc<dyn Hittable>,
+    keyframes: Vec<Keyframe>,
+    bbox: Aabb,
+}
+
+impl AnimatedInstance {
+    pub fn new(object: Arc<dyn Hittable>, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "animated instance needs at least one keyframe"
+        );
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        // Bound the object over the whole animation by sampling each segment, since rotating
+        // boxes sweep out more than the boxes at the keyframes. Between two samples a point
+        // travelling a path of length L stays within L / 2 of where it was at one of them, so
+        // padding by that much covers the motion in between.
+        let object_bbox = object.bounding_box();
+        let mut bbox = keyframes[0].transform().bounding_box(&object_bbox);
+        let mut padding = 0.0f64;
+        for pair in keyframes.windows(2) {
+            let mut previous = pair[0];
+            for step in 1..=MOTION_BOUNDS_STEPS {
+                let time = pair[0].time
+                    + (pair[1].time - pair[0].time) * step as f64 / MOTION_BOUNDS_STEPS as f64;
+                let current = Keyframe::interpolate(&pair[0], &pair[1], time);
+                bbox = Aabb::surrounding(&bbox, &current.transform().bounding_box(&object_bbox));
+                padding = padding.max(max_path_length(&previous, &current, &object_bbox) / 2.0);
+                previous = current;
+            }
+        }
+        let bbox = Aabb::new(
+            bbox.x.expand(2.0 * padding),
+            bbox.y.expand(2.0 * padding),
+            bbox.z.expand(2.0 * padding),
+        );
+
+        AnimatedInstance {
+            object,
+            keyframes,
+            bbox,
+        }
+    }
+
+    pub fn transform_at(&self, time: f64) -> Transform {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+
+        if next == 0 {
+            self.keyframes[0].transform()
+        } else if next == self.keyframes.len() {
+            self.keyframes[next - 1].transform()
+        } else {
+            Keyframe::interpolate(&self.keyframes[next - 1], &self.keyframes[next], time)
+                .transform()
+        }
+    }
+}
+
+impl Hittable for AnimatedInstance {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let transform = self.transform_at(r.time());
+        hit_transformed(
+            self.object.as_ref(),
+            &transform,
+            r,
+            ray_t,
+            |object, r, ray_t| object.hit(r, ray_t),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        let transform = self.transform_at(r.time());
+        intervals_transformed(self.object.as_ref(), &transform, r)
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let transform = self.transform_at(r.time());
+        hit_transformed(
+            self.object.as_ref(),
+            &transform,
+            r,
+            ray_t,
+            |object, r, ray_t| object.shadow_hit(r, ray_t),
+        )
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let object_r = self.transform_at(r.time()).inverse().ray(r);
+        self.object.transmittance(&object_r, ray_t)
+    }
+}
+
+// Upper bound on the length of the path any point of the box travels as the transform moves
+// from keyframe a to keyframe b, which interpolate linearly in translation and scale and at a
+// constant rate along the arc between the rotations
+fn max_path_length(a: &Keyframe, b: &Keyframe, object_bbox: &Aabb) -> f64 {
+    let translation = (b.translation - a.translation).length();
+
+    let cos_half_angle: f64 = (0..4).map(|i| a.rotation[i] * b.rotation[i]).sum();
+    let angle = 2.0 * cos_half_angle.abs().min(1.0).acos();
+
+    // The largest scaled offset of a box point from the origin, and the largest change in it
+    // from the scale alone, are both found at corners
+    let max_scale = Vec3::new(
+        a.scale.x().abs().max(b.scale.x().abs()),
+        a.scale.y().abs().max(b.scale.y().abs()),
+        a.scale.z().abs().max(b.scale.z().abs()),
+    );
+    let scale_change = b.scale - a.scale;
+    let (mut max_radius, mut max_scaling) = (0.0f64, 0.0f64);
+    for corner in 0..8 {
+        let c = [0, 1, 2].map(|axis| {
+            let extent = object_bbox.axis_interval(axis);
+            if corner & (1 << axis) == 0 {
+                extent.min
+            } else {
+                extent.max
+            }
+        });
+        let p = Vec3::new(c[0], c[1], c[2]);
+        max_radius = max_radius.max((max_scale * p).length());
+        max_scaling = max_scaling.max((scale_change * p).length());
+    }
+
+    translation + angle * max_radius + max_scaling
+}
+
+// Run a hit query on the object in its own space and bring the result back to world space
+fn hit_transformed<'a, F>(
+    object: &'a dyn Hittable,
+    transform: &Transform,
+    r: &Ray,
+    ray_t: Interval,
+    query: F,
+) -> Option<HitRecord<'a>>
+where
+    F: Fn(&'a dyn Hittable, &Ray, Interval) -> Option<HitRecord<'a>>,
+{
+    // Transform the ray from world space to object space. The direction is not normalized,
+    // so the ray parameter t is the same in both spaces.
+    let object_r = transform.inverse().ray(r);
+
+    let mut rec = query(object, &object_r, ray_t)?;
+    to_world(&mut rec, transform);
+
+    Some(rec)
+}
+
+fn intervals_transformed<'a>(
+    object: &'a dyn Hittable,
+    transform: &Transform,
+    r: &Ray,
+) -> Vec<(HitRecord<'a>, HitRecord<'a>)> {
+    let object_r = transform.inverse().ray(r);
+
+    let mut intervals = object.hit_intervals(&object_r);
+    for (entry, exit) in intervals.iter_mut() {
+        to_world(entry, transform);
+        to_world(exit, transform);
+    }
+
+    intervals
+}
+
+// Transform an intersection back from object space to world space. The normal already faces
+// against the object space ray, and the inverse transpose keeps it facing against the world
+// space ray.
+fn to_world(rec: &mut HitRecord, transform: &Transform) {
+    rec.p = transform.point(&rec.p);
+    rec.normal = transform.normal(&rec.normal).unit_vector();
+    if rec.tangent.length_squared() > 0.0 {
+        rec.tangent = transform.vector(&rec.tangent).unit_vector();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Material, sphere::Sphere, vec3::Point3};
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::lambertian(Color::new(0.5, 0.5, 0.5)),
+        ))
+    }
+
+    #[test]
+    fn instance_bounds_and_hits_follow_the_transform() {
+        let transform =
+            Transform::scale(2.0, 1.0, 1.0).then(&Transform::translate(Vec3::new(5.0, 0.0, 0.0)));
+        let instance = Instance::new(unit_sphere(), transform);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.x.min - 3.0).abs() < 1e-3 && (bbox.x.max - 7.0).abs() < 1e-3);
+        assert!((bbox.y.min + 1.0).abs() < 1e-3 && (bbox.y.max - 1.0).abs() < 1e-3);
+
+        let r = Ray::new(Point3::new(10.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = instance.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12);
+        assert!((rec.p - Point3::new(7.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((rec.normal - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12);
+
+        // From the center out to a point on the ellipsoid, where the normal is its gradient
+        let (sin, cos) = std::f64::consts::FRAC_PI_4.sin_cos();
+        let r = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(2.0 * cos, sin, 0.0));
+        let rec = instance.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        let outward = Vec3::new(cos / 2.0, sin, 0.0).unit_vector();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!(!rec.front_face);
+        assert!((rec.normal + outward).length() < 1e-12);
+    }
+
+    #[test]
+    fn animated_bounds_contain_the_object_at_every_time() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            Point3::new(3.0, 0.0, 0.0),
+            1.0,
+            Material::lambertian(Color::new(0.5, 0.5, 0.5)),
+        ));
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let keyframes = vec![
+            Keyframe::new(
+                0.0,
+                Vec3::new(0.0, 0.0, 0.0),
+                up,
+                0.0,
+                Vec3::new(1.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                0.5,
+                Vec3::new(1.0, 2.0, 0.0),
+                up,
+                170.0,
+                Vec3::new(1.5, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                1.0,
+                Vec3::new(0.0, 0.0, 3.0),
+                up,
+                340.0,
+                Vec3::new(1.0, 0.5, 2.0),
+            ),
+        ];
+        let instance = AnimatedInstance::new(sphere.clone(), keyframes);
+        let bbox = instance.bounding_box();
+
+        // Points on the sphere, swept through many times between and beyond the keyframes
+        for step in 0..=1000 {
+            let time = -0.1 + 1.2 * step as f64 / 1000.0;
+            let transform = instance.transform_at(time);
+            for k in 0..64 {
+                let (theta, phi) = (k as f64 * 0.7, k as f64 * 0.3);
+                let offset = Vec3::new(theta.cos() * phi.sin(), phi.cos(), theta.sin() * phi.sin());
+                let p = transform.point(&(Point3::new(3.0, 0.0, 0.0) + offset));
+                assert!(
+                    bbox.x.contains(p.x()) && bbox.y.contains(p.y()) && bbox.z.contains(p.z()),
+                    "{:?} escapes the bounds at time {}",
+                    p,
+                    time
+                );
+            }
+        }
+
+        // A ray at the midpoint time sees the sphere where the keyframe put it
+        let transform = instance.transform_at(0.5);
+        let center = transform.point(&Point3::new(3.0, 0.0, 0.0));
+        let r = Ray::with_time(center + Vec3::new(0.0, 10.0, 0.0), -up, 0.5);
+        let rec = instance.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 9.0).abs() < 1e-9);
+    }
+}
@@ -1,5 +1,6 @@
 use std::f64;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -10,6 +11,14 @@ impl Interval {
         Interval { min, max }
     }
 
+    // Create the interval tightly enclosing the two input intervals
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -26,6 +35,12 @@ impl Interval {
         x.clamp(self.min, self.max)
     }
 
+    // Pad the interval by delta/2 on each side
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     pub const EMPTY: Interval = Interval {
         min: f64::INFINITY,
         max: f64::NEG_INFINITY,
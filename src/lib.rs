@@ -0,0 +1,36 @@
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod constant_medium;
+pub mod csg;
+pub mod curve;
+pub mod displacement;
+pub mod gltf;
+pub mod heightfield;
+pub mod hittable;
+pub mod hittable_list;
+pub mod instance;
+pub mod interval;
+pub mod light;
+pub mod material;
+pub mod math;
+pub mod mesh;
+pub mod metaballs;
+pub mod noise;
+pub mod obj;
+pub mod onb;
+pub mod ply;
+pub mod quad;
+pub mod quadric;
+pub mod ray;
+pub mod sdf;
+pub mod sphere;
+pub mod subdivision;
+pub mod texture;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
+pub mod utils;
+pub mod vec3;
+pub mod volume;
@@ -0,0 +1,63 @@
+use rand::Rng;
+
+use crate::{
+    hittable::Hittable,
+    vec3::{Point3, Vec3},
+};
+
+// Emissive object that next event estimation can aim shadow rays at. Sphere and Quad are
+// lights when their material emits; emitters of other kinds are only found by scattered rays.
+pub trait Light {
+    // Probability density, per unit solid angle seen from origin, that random(origin, time)
+    // returns the given direction. Moving lights are seen as placed at the given time.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64;
+
+    // Random direction from origin toward the light as placed at the given time
+    fn random(&self, origin: &Point3, time: f64) -> Vec3;
+}
+
+// Lights of a scene, borrowed from the objects that make it up. Sampling picks one of them
+// uniformly.
+#[derive(Default)]
+pub struct LightList<'a> {
+    lights: Vec<&'a dyn Light>,
+}
+
+impl<'a> LightList<'a> {
+    // Collect every light in the world
+    pub fn new(world: &'a dyn Hittable) -> Self {
+        let mut lights = Vec::new();
+        world.collect_lights(&mut lights);
+        LightList { lights }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    // The average of the lights' densities, since each is picked with equal probability
+    pub fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_value(origin, direction, time))
+            .sum();
+        sum / self.lights.len() as f64
+    }
+
+    // Random direction toward one of the lights. There must be at least one.
+    pub fn random(&self, origin: &Point3, time: f64) -> Vec3 {
+        assert!(!self.lights.is_empty(), "no lights to sample");
+
+        let index = rand::thread_rng().gen_range(0..self.lights.len());
+        self.lights[index].random(origin, time)
+    }
+}
@@ -1,33 +1,29 @@
-mod camera;
-mod color;
-mod hittable;
-mod hittable_list;
-mod interval;
-mod material;
-mod ray;
-mod sphere;
-mod utils;
-mod vec3;
+use std::sync::Arc;
 
 use rand::Rng;
 
-use crate::camera::Camera;
-use crate::color::Color;
-use crate::hittable_list::HittableList;
-use crate::material::Material;
-use crate::sphere::Sphere;
-use crate::vec3::{Point3, Vec3};
+use raytracer::bvh::Bvh;
+use raytracer::camera::Camera;
+use raytracer::color::Color;
+use raytracer::hittable_list::HittableList;
+use raytracer::material::Material;
+use raytracer::quadric::Plane;
+use raytracer::sphere::Sphere;
+use raytracer::texture::CheckerTexture;
+use raytracer::vec3::{Point3, Vec3};
 
 fn main() -> std::io::Result<()> {
     let mut world = HittableList::new();
     let mut rng = rand::thread_rng();
 
+    let checker =
+        CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
     let ground_material = Material::Lambertian {
-        albedo: Color::new(0.5, 0.5, 0.5),
+        albedo: Arc::new(checker),
     };
-    world.add(Box::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
+    world.add(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
         ground_material,
     )));
 
@@ -44,12 +40,12 @@ fn main() -> std::io::Result<()> {
                 let sphere_material = if choose_mat < 0.8 {
                     // diffuse
                     let albedo = Color::random(0.0, 1.0) * Color::random(0.0, 1.0);
-                    Material::Lambertian { albedo }
+                    Material::lambertian(albedo)
                 } else if choose_mat < 0.95 {
                     // metal
                     let albedo = Color::random(0.5, 1.0);
                     let fuzz = rng.gen_range(0.0..0.5);
-                    Material::Metal { albedo, fuzz }
+                    Material::metal(albedo, fuzz)
                 } else {
                     // glass
                     Material::Dielectric {
@@ -71,25 +67,23 @@ fn main() -> std::io::Result<()> {
         material1,
     )));
 
-    let material2 = Material::Lambertian {
-        albedo: Color::new(0.4, 0.2, 0.1),
-    };
+    let material2 = Material::lambertian(Color::new(0.4, 0.2, 0.1));
     world.add(Box::new(Sphere::new(
         Point3::new(-4.0, 1.0, 0.0),
         1.0,
         material2,
     )));
 
-    let material3 = Material::Metal {
-        albedo: Color::new(0.7, 0.6, 0.5),
-        fuzz: 0.0,
-    };
+    let material3 = Material::metal(Color::new(0.7, 0.6, 0.5), 0.0);
     world.add(Box::new(Sphere::new(
         Point3::new(4.0, 1.0, 0.0),
         1.0,
         material3,
     )));
 
+    let world = Bvh::new(world);
+    println!("{}", world.stats());
+
     let mut cam = Camera::new();
 
     cam.aspect_ratio = 16.0 / 9.0;
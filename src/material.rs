@@ -1,60 +1,216 @@
+use std::sync::Arc;
+
 use rand::Rng;
 
-use crate::{color::Color, hittable::HitRecord, ray::Ray, vec3::Vec3};
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    onb::Onb,
+    ray::Ray,
+    texture::{scalar_value, SolidColor, Texture},
+    utils::PI,
+    vec3::Vec3,
+};
+
+// Fuzz below which metal is a perfect mirror, whose lobe is too narrow to sample as smooth
+const MIN_FUZZ: f64 = 1e-3;
 
-#[derive(Clone, Copy)]
+// Lambertian and Metal parameters are textures evaluated at the hit. Metal fuzz is the average
+// of its texture's channels, so any greyscale texture can drive it.
+#[derive(Clone)]
 pub enum Material {
-    Lambertian { albedo: Color },
-    Metal { albedo: Color, fuzz: f64 },
-    Dielectric { refraction_index: f64 },
+    Lambertian {
+        albedo: Arc<dyn Texture>,
+    },
+    Metal {
+        albedo: Arc<dyn Texture>,
+        fuzz: Arc<dyn Texture>,
+    },
+    Dielectric {
+        refraction_index: f64,
+    },
+    // Emits its texture's color times the intensity, and scatters nothing
+    DiffuseLight {
+        emit: Arc<dyn Texture>,
+        intensity: f64,
+    },
+    // Phase function of participating media
+    Isotropic {
+        albedo: Color,
+    },
+}
+
+// Which kind of lobe a scattered direction came from
+#[derive(Debug, Clone, Copy)]
+pub enum Lobe {
+    // Spread over directions, with the given density per unit solid angle
+    Smooth { pdf: f64 },
+    // A single direction, such as a mirror reflection or a refraction, whose density is a delta
+    // and which no other sampling strategy can find
+    Specular,
+}
+
+// Direction chosen by Material::sample
+#[derive(Debug, Clone, Copy)]
+pub struct ScatterSample {
+    pub direction: Vec3,
+    // Throughput along the direction: eval / pdf for smooth lobes
+    pub weight: Color,
+    pub lobe: Lobe,
 }
 
 impl Material {
-    // Returns None if ray absorbed, otherwise returns Some((attenuation, scattered))
-    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        match *self {
-            Material::Lambertian { albedo } => Self::scatter_lambertian(albedo, rec),
-            Material::Metal { albedo, fuzz } => Self::scatter_metal(albedo, fuzz, r_in, rec),
+    // Lambertian material with a constant albedo
+    pub fn lambertian(albedo: Color) -> Self {
+        Material::Lambertian {
+            albedo: Arc::new(SolidColor::new(albedo)),
+        }
+    }
+
+    // Metal with a constant albedo and fuzz
+    pub fn metal(albedo: Color, fuzz: f64) -> Self {
+        Material::Metal {
+            albedo: Arc::new(SolidColor::new(albedo)),
+            fuzz: Arc::new(SolidColor::from_rgb(fuzz, fuzz, fuzz)),
+        }
+    }
+
+    // Light of a constant color
+    pub fn diffuse_light(emit: Color, intensity: f64) -> Self {
+        Material::DiffuseLight {
+            emit: Arc::new(SolidColor::new(emit)),
+            intensity,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::DiffuseLight { .. })
+    }
+
+    // Light given off at the hit, black for materials that don't emit
+    pub fn emitted(&self, rec: &HitRecord) -> Color {
+        match self {
+            Material::DiffuseLight { emit, intensity } => *intensity * emit.value_at(rec),
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Samples a direction for light arriving along r_in to scatter into, or returns None if the
+    // light is absorbed
+    pub fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterSample> {
+        match self {
+            Material::Lambertian { albedo } => {
+                let albedo = albedo.value_at(rec);
+                Some(Self::sample_lambertian(albedo, rec))
+            }
+            Material::Metal { albedo, fuzz } => {
+                let albedo = albedo.value_at(rec);
+                Self::sample_metal(albedo, fuzz_at(fuzz.as_ref(), rec), r_in, rec)
+            }
             Material::Dielectric { refraction_index } => {
-                Self::scatter_dielectric(refraction_index, r_in, rec)
+                Some(Self::sample_dielectric(*refraction_index, r_in, rec))
             }
+            Material::Isotropic { albedo } => Some(Self::sample_isotropic(*albedo)),
+            Material::DiffuseLight { .. } => None,
         }
     }
 
-    fn scatter_lambertian(albedo: Color, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
+    // Fraction of light arriving from direction that scatters out along -r_in, per unit solid
+    // angle: the BSDF times the cosine at the surface. Specular lobes can only be reached
+    // through sample(), so they contribute nothing here.
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> Color {
+        match self {
+            Material::Lambertian { albedo } => {
+                let albedo = albedo.value_at(rec);
+                self.pdf(r_in, rec, direction) * albedo
+            }
+            Material::Metal { albedo, .. } => {
+                // Light reflected below the surface is lost
+                if rec.normal.dot(direction) <= 0.0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+                let albedo = albedo.value_at(rec);
+                self.pdf(r_in, rec, direction) * albedo
+            }
+            Material::Isotropic { albedo } => self.pdf(r_in, rec, direction) * *albedo,
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
 
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+    // Density, per unit solid angle, with which sample() returns direction from a smooth lobe.
+    // Zero for specular lobes.
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: &Vec3) -> f64 {
+        match self {
+            Material::Lambertian { .. } => {
+                let cos_theta = rec.normal.dot(&direction.unit_vector());
+                cos_theta.max(0.0) / PI
+            }
+            Material::Metal { fuzz, .. } => {
+                let fuzz = fuzz_at(fuzz.as_ref(), rec);
+                if fuzz < MIN_FUZZ {
+                    return 0.0;
+                }
+                let reflected = Vec3::reflect(&r_in.direction().unit_vector(), &rec.normal);
+                glossy_pdf(&reflected, direction, phong_exponent(fuzz))
+            }
+            Material::Isotropic { .. } => 1.0 / (4.0 * PI),
+            _ => 0.0,
         }
+    }
+
+    fn sample_lambertian(albedo: Color, rec: &HitRecord) -> ScatterSample {
+        // Cosine weighted, so the cosine and 1 / pi of the BSDF cancel against the density
+        let uvw = Onb::new(&rec.normal);
+        let direction = uvw.transform(&Vec3::random_cosine_direction());
+        let pdf = direction.dot(&uvw.w()).max(0.0) / PI;
 
-        let scattered = Ray::new(rec.p, scatter_direction);
-        Some((albedo, scattered))
+        ScatterSample {
+            direction,
+            weight: albedo,
+            lobe: Lobe::Smooth { pdf },
+        }
     }
 
-    fn scatter_metal(
+    fn sample_metal(
         albedo: Color,
         fuzz: f64,
         r_in: &Ray,
         rec: &HitRecord,
-    ) -> Option<(Color, Ray)> {
-        let reflected = Vec3::reflect(&r_in.direction(), &rec.normal);
-        let reflected = reflected.unit_vector() + (fuzz * Vec3::random_unit_vector());
-        let scattered = Ray::new(rec.p, reflected);
+    ) -> Option<ScatterSample> {
+        let reflected = Vec3::reflect(&r_in.direction().unit_vector(), &rec.normal);
+        if fuzz < MIN_FUZZ {
+            return Some(ScatterSample {
+                direction: reflected,
+                weight: albedo,
+                lobe: Lobe::Specular,
+            });
+        }
 
-        if scattered.direction().dot(&rec.normal) > 0.0 {
-            Some((albedo, scattered))
-        } else {
-            None
+        // Phong lobe around the mirror direction. Its density is the BSDF times the cosine, up
+        // to the albedo, so the weight is just the albedo.
+        let exponent = phong_exponent(fuzz);
+        let mut rng = rand::thread_rng();
+        let cos_alpha = (1.0 - rng.gen::<f64>()).powf(1.0 / (exponent + 1.0));
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let local = Vec3::new(phi.cos() * sin_alpha, phi.sin() * sin_alpha, cos_alpha);
+        let direction = Onb::new(&reflected).transform(&local);
+
+        // Directions below the surface are absorbed
+        if direction.dot(&rec.normal) <= 0.0 {
+            return None;
         }
+
+        Some(ScatterSample {
+            direction,
+            weight: albedo,
+            lobe: Lobe::Smooth {
+                pdf: glossy_pdf(&reflected, &direction, exponent),
+            },
+        })
     }
 
-    fn scatter_dielectric(
-        refraction_index: f64,
-        r_in: &Ray,
-        rec: &HitRecord,
-    ) -> Option<(Color, Ray)> {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
+    fn sample_dielectric(refraction_index: f64, r_in: &Ray, rec: &HitRecord) -> ScatterSample {
         let ri = if rec.front_face {
             1.0 / refraction_index
         } else {
@@ -75,9 +231,44 @@ impl Material {
             Vec3::refract(&unit_direction, &rec.normal, ri)
         };
 
-        let scattered = Ray::new(rec.p, direction);
-        Some((attenuation, scattered))
+        ScatterSample {
+            direction,
+            weight: Color::new(1.0, 1.0, 1.0),
+            lobe: Lobe::Specular,
+        }
+    }
+
+    fn sample_isotropic(albedo: Color) -> ScatterSample {
+        // Scatter uniformly in all directions
+        ScatterSample {
+            direction: Vec3::random_unit_vector(),
+            weight: albedo,
+            lobe: Lobe::Smooth {
+                pdf: 1.0 / (4.0 * PI),
+            },
+        }
+    }
+}
+
+// Metal fuzz at the hit, the average of its texture's channels
+fn fuzz_at(fuzz: &dyn Texture, rec: &HitRecord) -> f64 {
+    scalar_value(fuzz, rec).clamp(0.0, 1.0)
+}
+
+// Exponent of the Phong lobe used for metal with the given fuzz. The lobe is roughly fuzz
+// radians wide, as wide as the offset the fuzz used to add to the mirror direction.
+fn phong_exponent(fuzz: f64) -> f64 {
+    1.0 / (fuzz * fuzz) - 1.0
+}
+
+// Density over the sphere of directions of a Phong lobe around the unit vector axis:
+// (exponent + 1) / (2 pi) * cos^exponent of the angle to the axis, zero beyond 90 degrees
+fn glossy_pdf(axis: &Vec3, direction: &Vec3, exponent: f64) -> f64 {
+    let cos_alpha = axis.dot(&direction.unit_vector());
+    if cos_alpha <= 0.0 {
+        return 0.0;
     }
+    (exponent + 1.0) / (2.0 * PI) * cos_alpha.powf(exponent)
 }
 
 fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
@@ -87,3 +278,77 @@ fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
 
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Point3;
+
+    // Hit on the xz plane facing up, for a ray coming in at an angle
+    fn hit(mat: &Material) -> (Ray, HitRecord<'_>) {
+        let r = Ray::new(Point3::new(-1.0, 2.0, 0.0), Vec3::new(1.0, -2.0, 0.0));
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let rec = HitRecord::new(Point3::new(0.0, 0.0, 0.0), 1.0, &r, &up, mat);
+        (r, rec)
+    }
+
+    fn smooth_materials() -> Vec<Material> {
+        vec![
+            Material::lambertian(Color::new(0.8, 0.5, 0.2)),
+            Material::metal(Color::new(0.9, 0.8, 0.7), 0.3),
+            Material::metal(Color::new(0.9, 0.8, 0.7), 1.0),
+            Material::Isotropic {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            },
+        ]
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        // Midpoint rule over equal area cells of the sphere: uniform in z and in the angle
+        let (nz, nphi) = (1000, 1000);
+        let cell_area = (2.0 / nz as f64) * (2.0 * PI / nphi as f64);
+
+        for mat in smooth_materials() {
+            let (r, rec) = hit(&mat);
+            let mut total = 0.0;
+            for i in 0..nz {
+                let z = -1.0 + 2.0 * (i as f64 + 0.5) / nz as f64;
+                let radius = (1.0 - z * z).sqrt();
+                for j in 0..nphi {
+                    let phi = 2.0 * PI * (j as f64 + 0.5) / nphi as f64;
+                    let direction = Vec3::new(radius * phi.cos(), z, radius * phi.sin());
+                    total += mat.pdf(&r, &rec, &direction) * cell_area;
+                }
+            }
+            assert!((total - 1.0).abs() < 1e-2, "pdf integrates to {total}");
+        }
+    }
+
+    #[test]
+    fn sample_weight_matches_eval_over_pdf() {
+        for mat in smooth_materials() {
+            let (r, rec) = hit(&mat);
+            for _ in 0..1000 {
+                let Some(sample) = mat.sample(&r, &rec) else {
+                    continue;
+                };
+                let Lobe::Smooth { pdf } = sample.lobe else {
+                    panic!("smooth material returned a specular sample");
+                };
+                assert!((pdf - mat.pdf(&r, &rec, &sample.direction)).abs() <= 1e-9 * pdf);
+                if pdf > 1e-6 {
+                    let expected = mat.eval(&r, &rec, &sample.direction) / pdf;
+                    assert!((sample.weight - expected).length() < 1e-9);
+                }
+            }
+        }
+
+        // A mirror stays a delta lobe
+        let mirror = Material::metal(Color::new(0.9, 0.9, 0.9), 0.0);
+        let (r, rec) = hit(&mirror);
+        let sample = mirror.sample(&r, &rec).unwrap();
+        assert!(matches!(sample.lobe, Lobe::Specular));
+        assert_eq!(mirror.pdf(&r, &rec, &sample.direction), 0.0);
+    }
+}
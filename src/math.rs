@@ -0,0 +1,323 @@
+// Real roots of polynomials, for intersecting rays with implicit surfaces. Each solver takes
+// the coefficients from the highest power down and returns the real roots in increasing order,
+// falling back to the lower degree solver when the leading coefficient vanishes.
+
+use crate::utils::PI;
+
+// Number of Newton iterations used to polish the roots of cubics and quartics
+const POLISH_ITERATIONS: usize = 4;
+
+// Limit on the number of halvings used to pin down a root, enough to reach full precision
+const BISECTION_ITERATIONS: usize = 100;
+
+// Roots of a x^2 + b x + c = 0
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return Vec::new();
+        }
+        return vec![-c / b];
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    // Avoid cancellation between b and the square root by computing the larger magnitude root
+    // first and deriving the other one from the product of the roots
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        // b and c are both zero
+        return vec![0.0, 0.0];
+    }
+
+    let (t0, t1) = (q / a, c / q);
+    vec![t0.min(t1), t0.max(t1)]
+}
+
+// Roots of a x^3 + b x^2 + c x + d = 0
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d);
+    }
+
+    let (b, c, d) = (b / a, c / a, d / a);
+    let q = (b * b - 3.0 * c) / 9.0;
+    let r = (2.0 * b * b * b - 9.0 * b * c + 27.0 * d) / 54.0;
+    let q3 = q * q * q;
+
+    let mut roots = if r * r < q3 {
+        // Three real roots, from the trigonometric form
+        let theta = (r / q3.sqrt()).clamp(-1.0, 1.0).acos();
+        let s = -2.0 * q.sqrt();
+        vec![
+            s * (theta / 3.0).cos() - b / 3.0,
+            s * ((theta + 2.0 * PI) / 3.0).cos() - b / 3.0,
+            s * ((theta - 2.0 * PI) / 3.0).cos() - b / 3.0,
+        ]
+    } else {
+        // One real root, from Cardano's formula
+        let e = -r.signum() * (r.abs() + (r * r - q3).sqrt()).cbrt();
+        let f = if e == 0.0 { 0.0 } else { q / e };
+        vec![e + f - b / 3.0]
+    };
+
+    let coefficients = [1.0, b, c, d];
+    for root in roots.iter_mut() {
+        *root = polish_root(&coefficients, *root);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// Roots of a x^4 + b x^3 + c x^2 + d x + e = 0, by Ferrari's method. The roots are polished
+// with Newton iterations on the original polynomial, which recovers the precision lost in the
+// resolvent cubic.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    // Substitute x = y - b/4 to get the depressed quartic y^4 + p y^2 + q y + r = 0
+    let shift = -b / 4.0;
+    let b2 = b * b;
+    let p = c - 3.0 * b2 / 8.0;
+    let q = d - b * c / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * c / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let mut ys = Vec::with_capacity(4);
+    let scale = 1.0 + p.abs() + r.abs();
+    if q.abs() <= 1e-12 * scale {
+        // Biquadratic: solve for y^2
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                let y = z.sqrt();
+                ys.extend([-y, y]);
+            }
+        }
+    } else {
+        // Complete the square: (y^2 + p/2 + m)^2 = 2m (y - q/(4m))^2, where m is a positive
+        // root of the resolvent cubic 8 m^3 + 8 p m^2 + (2 p^2 - 8 r) m - q^2 = 0. The cubic is
+        // negative at zero, so its largest root is positive.
+        let m = solve_cubic(8.0, 8.0 * p, 2.0 * p * p - 8.0 * r, -q * q)
+            .last()
+            .copied()
+            .unwrap_or(0.0);
+        if m <= 0.0 {
+            return Vec::new();
+        }
+
+        let s = (2.0 * m).sqrt();
+        let k = s * q / (4.0 * m);
+        ys.extend(solve_quadratic(1.0, -s, p / 2.0 + m + k));
+        ys.extend(solve_quadratic(1.0, s, p / 2.0 + m - k));
+    }
+
+    let coefficients = [1.0, b, c, d, e];
+    let mut roots: Vec<f64> = ys
+        .into_iter()
+        .map(|y| polish_root(&coefficients, y + shift))
+        .collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// Roots in [min, max] of the polynomial of any degree with the given coefficients, where it
+// changes sign. The roots of the derivative split the range into pieces on which the polynomial
+// is monotonic, and each piece holding a sign change is bisected, so no root is skipped however
+// close together the roots are.
+pub fn solve_polynomial_in(coefficients: &[f64], min: f64, max: f64) -> Vec<f64> {
+    let first = coefficients.iter().position(|&c| c != 0.0);
+    let coefficients = match first {
+        Some(first) if first + 1 < coefficients.len() => &coefficients[first..],
+        _ => return Vec::new(),
+    };
+
+    let degree = coefficients.len() - 1;
+    let derivative: Vec<f64> = coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| c * (degree - i) as f64)
+        .collect();
+
+    let mut ends = vec![min];
+    ends.extend(solve_polynomial_in(&derivative, min, max));
+    ends.push(max);
+
+    let mut roots: Vec<f64> = Vec::new();
+    for piece in ends.windows(2) {
+        let (mut lo, mut hi) = (piece[0], piece[1]);
+        let (mut f_lo, f_hi) = (evaluate(coefficients, lo).0, evaluate(coefficients, hi).0);
+        if f_lo == 0.0 {
+            if roots.last() != Some(&lo) {
+                roots.push(lo);
+            }
+            continue;
+        }
+        if f_hi == 0.0 {
+            roots.push(hi);
+            continue;
+        }
+        if f_lo.signum() == f_hi.signum() {
+            continue;
+        }
+
+        for _ in 0..BISECTION_ITERATIONS {
+            let mid = 0.5 * (lo + hi);
+            if mid <= lo || mid >= hi {
+                break;
+            }
+            let f_mid = evaluate(coefficients, mid).0;
+            if f_mid == 0.0 {
+                (lo, hi) = (mid, mid);
+                break;
+            }
+            if f_mid.signum() == f_lo.signum() {
+                (lo, f_lo) = (mid, f_mid);
+            } else {
+                hi = mid;
+            }
+        }
+        roots.push(0.5 * (lo + hi));
+    }
+
+    roots
+}
+
+// Refine a root estimate with Newton's method, keeping the estimate if an iteration would make
+// it worse, e.g. at a double root where the derivative vanishes
+fn polish_root(coefficients: &[f64], mut x: f64) -> f64 {
+    let mut best = evaluate(coefficients, x).0.abs();
+
+    for _ in 0..POLISH_ITERATIONS {
+        let (value, derivative) = evaluate(coefficients, x);
+        if derivative == 0.0 {
+            break;
+        }
+
+        let next = x - value / derivative;
+        let next_value = evaluate(coefficients, next).0.abs();
+        if next_value.is_nan() || next_value >= best {
+            break;
+        }
+        x = next;
+        best = next_value;
+    }
+
+    x
+}
+
+// Value and derivative of the polynomial at x, by Horner's method
+fn evaluate(coefficients: &[f64], x: f64) -> (f64, f64) {
+    let mut value = 0.0;
+    let mut derivative = 0.0;
+    for &coefficient in coefficients {
+        derivative = derivative * x + value;
+        value = value * x + coefficient;
+    }
+
+    (value, derivative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Coefficients of the monic polynomial with the given roots
+    fn from_roots(roots: &[f64]) -> Vec<f64> {
+        let mut coefficients = vec![1.0];
+        for &root in roots {
+            let mut next = coefficients.clone();
+            next.push(0.0);
+            for (i, &c) in coefficients.iter().enumerate() {
+                next[i + 1] -= root * c;
+            }
+            coefficients = next;
+        }
+        coefficients
+    }
+
+    fn assert_roots(found: &[f64], expected: &[f64], tolerance: f64) {
+        assert_eq!(
+            found.len(),
+            expected.len(),
+            "found {found:?}, expected {expected:?}"
+        );
+        for (f, e) in found.iter().zip(expected) {
+            assert!(
+                (f - e).abs() <= tolerance * e.abs().max(1.0),
+                "found {found:?}, expected {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(&solve_quadratic(1.0, -3.0, 2.0), &[1.0, 2.0], 1e-12);
+        assert_roots(&solve_quadratic(0.0, 2.0, -4.0), &[2.0], 1e-12);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_empty());
+
+        // Widely separated roots that lose precision with the textbook formula
+        assert_roots(&solve_quadratic(1.0, -1e8, 1.0), &[1e-8, 1e8], 1e-12);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        let c = from_roots(&[-2.0, 0.5, 3.0]);
+        assert_roots(
+            &solve_cubic(c[0], c[1], c[2], c[3]),
+            &[-2.0, 0.5, 3.0],
+            1e-10,
+        );
+
+        // x^3 - 8 has a single real root
+        assert_roots(&solve_cubic(1.0, 0.0, 0.0, -8.0), &[2.0], 1e-12);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        for roots in [
+            [-3.0, -1.0, 2.0, 5.0],
+            [0.1, 0.2, 0.3, 0.4],
+            [-100.0, -99.5, 99.5, 100.0],
+            [1.0, 2.0, 1000.0, 1001.0],
+        ] {
+            let c = from_roots(&roots);
+            assert_roots(&solve_quartic(c[0], c[1], c[2], c[3], c[4]), &roots, 1e-8);
+        }
+
+        // Biquadratic x^4 - 5 x^2 + 4
+        assert_roots(
+            &solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0),
+            &[-2.0, -1.0, 1.0, 2.0],
+            1e-12,
+        );
+
+        // (x^2 + 1)(x - 1)(x - 2) has two real roots
+        let c = [1.0, -3.0, 3.0, -3.0, 2.0];
+        assert_roots(
+            &solve_quartic(c[0], c[1], c[2], c[3], c[4]),
+            &[1.0, 2.0],
+            1e-10,
+        );
+
+        // No real roots
+        assert!(solve_quartic(1.0, 0.0, 2.0, 0.0, 1.5).is_empty());
+    }
+
+    #[test]
+    fn polynomial_roots_in_range() {
+        let roots = [-2.5, -0.1, 0.0, 0.3, 0.31, 1.7];
+        let c = from_roots(&roots);
+        assert_roots(&solve_polynomial_in(&c, -10.0, 10.0), &roots, 1e-10);
+        assert_roots(&solve_polynomial_in(&c, 0.2, 1.0), &[0.3, 0.31], 1e-10);
+
+        // (x^2 + 1)^3 has no real roots, and constants have none either
+        let c = [1.0, 0.0, 3.0, 0.0, 3.0, 0.0, 1.0];
+        assert!(solve_polynomial_in(&c, -10.0, 10.0).is_empty());
+        assert!(solve_polynomial_in(&[0.0, 0.0, 2.0], -1.0, 1.0).is_empty());
+    }
+}
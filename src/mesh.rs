@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::{BvhStats, BvhTree},
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    triangle::intersect_triangle,
+    vec3::{Point3, Vec3},
+};
+
+// Per-vertex attribute buffers that can be shared between several meshes, e.g. one mesh per
+// material of the same model. The normals, uvs and colors buffers are either empty or hold one
+// entry per position. Colors are interpolated into HitRecord::color, where a
+// VertexColorTexture picks them up.
+#[derive(Debug, Clone, Default)]
+pub struct MeshBuffers {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<[f64; 2]>,
+    pub colors: Vec<Color>,
+}
+
+impl MeshBuffers {
+    pub fn new(positions: Vec<Point3>) -> Self {
+        MeshBuffers {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            colors: Vec::new(),
+        }
+    }
+
+    pub fn has_normals(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    pub fn has_uvs(&self) -> bool {
+        !self.uvs.is_empty()
+    }
+
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+}
+
+// Indexed triangle mesh with a single material. The triangles are kept in their own BVH, so
+// the whole mesh is one object in a HittableList no matter how many faces it has.
+pub struct TriangleMesh {
+    buffers: Arc<MeshBuffers>,
+    triangles: Vec<[usize; 3]>,
+    mat: Material,
+    tree: BvhTree,
+}
+
+impl TriangleMesh {
+    // Panics if the buffers and triangles are inconsistent; use try_new for untrusted data
+    pub fn new(buffers: Arc<MeshBuffers>, triangles: Vec<[usize; 3]>, mat: Material) -> Self {
+        TriangleMesh::try_new(buffers, triangles, mat)
+            .unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    // Build a mesh, or describe why the attribute counts or triangle indices don't fit the
+    // positions
+    pub fn try_new(
+        buffers: Arc<MeshBuffers>,
+        triangles: Vec<[usize; 3]>,
+        mat: Material,
+    ) -> Result<Self, String> {
+        let vertex_count = buffers.positions.len();
+        let counts = [
+            ("normal", buffers.normals.len()),
+            ("uv", buffers.uvs.len()),
+            ("color", buffers.colors.len()),
+        ];
+        for (name, count) in counts {
+            if count != 0 && count != vertex_count {
+                return Err(format!(
+                    "mesh has {} {}s for {} positions",
+                    count, name, vertex_count
+                ));
+            }
+        }
+        if let Some(index) = triangles.iter().flatten().find(|&&i| i >= vertex_count) {
+            return Err(format!(
+                "mesh triangle index {} out of range ({} vertices)",
+                index, vertex_count
+            ));
+        }
+
+        let bounds: Vec<Aabb> = triangles
+            .iter()
+            .map(|&[i0, i1, i2]| {
+                let p = &buffers.positions;
+                Aabb::surrounding(
+                    &Aabb::from_points(p[i0], p[i1]),
+                    &Aabb::from_points(p[i2], p[i2]),
+                )
+            })
+            .collect();
+
+        Ok(TriangleMesh {
+            buffers,
+            triangles,
+            mat,
+            tree: BvhTree::new(&bounds),
+        })
+    }
+
+    pub fn buffers(&self) -> &Arc<MeshBuffers> {
+        &self.buffers
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        self.tree.stats()
+    }
+
+    fn hit_triangle(&self, index: usize, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let [i0, i1, i2] = self.triangles[index];
+        let buffers = &self.buffers;
+        let (p0, p1, p2) = (
+            buffers.positions[i0],
+            buffers.positions[i1],
+            buffers.positions[i2],
+        );
+
+        let (t, b) = intersect_triangle(r, ray_t, p0, p1, p2)?;
+
+        let geometric_normal = (p1 - p0).cross(&(p2 - p0));
+        if geometric_normal.length_squared() == 0.0 {
+            // Degenerate triangle
+            return None;
+        }
+
+        let mut rec = HitRecord::new(r.at(t), t, r, &geometric_normal.unit_vector(), &self.mat);
+
+        if buffers.has_normals() {
+            let n = b[0] * buffers.normals[i0]
+                + b[1] * buffers.normals[i1]
+                + b[2] * buffers.normals[i2];
+            if n.length_squared() > 0.0 {
+                rec.set_shading_normal(&n.unit_vector());
+            }
+        }
+
+        if buffers.has_uvs() {
+            let (uv0, uv1, uv2) = (buffers.uvs[i0], buffers.uvs[i1], buffers.uvs[i2]);
+            rec.u = b[0] * uv0[0] + b[1] * uv1[0] + b[2] * uv2[0];
+            rec.v = b[0] * uv0[1] + b[1] * uv1[1] + b[2] * uv2[1];
+        } else {
+            rec.u = b[1];
+            rec.v = b[2];
+        }
+
+        if buffers.has_colors() {
+            rec.color =
+                b[0] * buffers.colors[i0] + b[1] * buffers.colors[i1] + b[2] * buffers.colors[i2];
+        }
+
+        Some(rec)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.tree
+            .hit(r, ray_t, |i, r, ray_t| self.hit_triangle(i, r, ray_t))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    fn mesh(positions: Vec<Point3>, triangles: Vec<[usize; 3]>) -> TriangleMesh {
+        TriangleMesh::new(Arc::new(MeshBuffers::new(positions)), triangles, material())
+    }
+
+    fn hits(mesh: &TriangleMesh, origin: Point3, target: Point3) -> bool {
+        let r = Ray::new(origin, target - origin);
+        mesh.hit(&r, Interval::new(0.0, f64::INFINITY)).is_some()
+    }
+
+    #[test]
+    fn rays_through_shared_edges_and_vertices_hit() {
+        // Fan of six skewed triangles around a center vertex
+        let center = Point3::new(0.13, -0.27, 0.31);
+        let mut positions = vec![center];
+        for k in 0..6 {
+            let a = k as f64 * std::f64::consts::PI / 3.0 + 0.1;
+            positions.push(center + Vec3::new(a.cos(), a.sin(), 0.2 * a.sin()));
+        }
+        let triangles = (0..6).map(|k| [0, 1 + k, 1 + (k + 1) % 6]).collect();
+        let fan = mesh(positions.clone(), triangles);
+
+        let origin = Point3::new(0.7, 0.4, 3.0);
+        assert!(hits(&fan, origin, center));
+        for k in 0..6 {
+            let rim = positions[1 + k];
+            for step in 1..200 {
+                let s = step as f64 / 200.0;
+                assert!(
+                    hits(&fan, origin, center + s * (rim - center)),
+                    "edge {} at {}",
+                    k,
+                    s
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_area_triangles_are_never_hit() {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(2.0, 2.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+        ];
+        let origin = Point3::new(1.0, 1.0, 1.0);
+        let target = Point3::new(1.0, 1.0, 0.0);
+
+        let sliver = mesh(positions.clone(), vec![[0, 1, 2]]);
+        assert!(!hits(&sliver, origin, target));
+
+        // The sliver doesn't hide the real triangle sharing its edge
+        let both = mesh(positions, vec![[0, 1, 2], [0, 2, 3]]);
+        let r = Ray::new(origin, target - origin);
+        let rec = both.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn try_new_rejects_inconsistent_buffers() {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+
+        let mut buffers = MeshBuffers::new(positions.clone());
+        buffers.normals = vec![Vec3::new(0.0, 0.0, 1.0); 2];
+        let error = TriangleMesh::try_new(Arc::new(buffers), vec![[0, 1, 2]], material());
+        assert_eq!(
+            error.err().as_deref(),
+            Some("mesh has 2 normals for 3 positions")
+        );
+
+        let buffers = Arc::new(MeshBuffers::new(positions));
+        let error = TriangleMesh::try_new(buffers, vec![[0, 1, 3]], material());
+        assert_eq!(
+            error.err().as_deref(),
+            Some("mesh triangle index 3 out of range (3 vertices)")
+        );
+    }
+}
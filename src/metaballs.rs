@@ -0,0 +1,288 @@
+use crate::{
+    aabb::Aabb,
+    bvh::BvhTree,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    math::solve_polynomial_in,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+// Point source of the metaball field. Its contribution falls off smoothly from weight at the
+// center to zero at the influence radius, and is zero beyond it.
+#[derive(Debug, Clone, Copy)]
+pub struct Metaball {
+    pub center: Point3,
+    pub radius: f64,
+    pub weight: f64,
+}
+
+impl Metaball {
+    // The radius is checked when the ball is added to Metaballs
+    pub fn new(center: Point3, radius: f64, weight: f64) -> Self {
+        Metaball {
+            center,
+            radius,
+            weight,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - rvec, self.center + rvec)
+    }
+}
+
+// Blobby surface where the summed field of a set of metaballs equals the threshold. Each ball
+// contributes weight * (1 - d^2 / radius^2)^3 at distance d, so along a ray the field is a
+// polynomial of degree 6 between the points where the ray enters or leaves an influence sphere,
+// and its first crossing of the threshold is found exactly. Negative weights carve blobs out.
+pub struct Metaballs {
+    balls: Vec<Metaball>,
+    threshold: f64,
+    mat: Material,
+    tree: BvhTree,
+}
+
+impl Metaballs {
+    // Panics on a non-positive threshold or radius; use try_new for untrusted parameters
+    pub fn new(balls: Vec<Metaball>, threshold: f64, mat: Material) -> Self {
+        Metaballs::try_new(balls, threshold, mat).unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    pub fn try_new(balls: Vec<Metaball>, threshold: f64, mat: Material) -> Result<Self, String> {
+        if !(threshold > 0.0 && threshold.is_finite()) {
+            return Err(format!("metaball threshold {} must be positive", threshold));
+        }
+        if let Some((i, ball)) = balls
+            .iter()
+            .enumerate()
+            .find(|(_, ball)| !(ball.radius > 0.0 && ball.radius.is_finite()))
+        {
+            return Err(format!(
+                "metaball {} radius {} must be positive",
+                i, ball.radius
+            ));
+        }
+
+        let bounds: Vec<Aabb> = balls.iter().map(Metaball::bounding_box).collect();
+        Ok(Metaballs {
+            balls,
+            threshold,
+            mat,
+            tree: BvhTree::new(&bounds),
+        })
+    }
+
+    // Field value at p
+    pub fn field(&self, p: &Point3) -> f64 {
+        self.balls
+            .iter()
+            .map(|ball| {
+                let q = (*p - ball.center).length_squared() / (ball.radius * ball.radius);
+                if q < 1.0 {
+                    ball.weight * (1.0 - q).powi(3)
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+
+    // Gradient of the field at p, from the balls in the given list
+    fn gradient(&self, p: &Point3, candidates: &[(usize, f64, f64)]) -> Vec3 {
+        candidates
+            .iter()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &(i, _, _)| {
+                let ball = &self.balls[i];
+                let r2 = ball.radius * ball.radius;
+                let offset = *p - ball.center;
+                let q = offset.length_squared() / r2;
+                if q < 1.0 {
+                    sum + (-6.0 * ball.weight * (1.0 - q).powi(2) / r2) * offset
+                } else {
+                    sum
+                }
+            })
+    }
+}
+
+impl Hittable for Metaballs {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // Collect every ball whose influence sphere the ray passes through, with the part of the
+        // ray inside it. The callback never reports a hit, so the tree visits all of them.
+        let a = r.direction().length_squared();
+        let mut candidates: Vec<(usize, f64, f64)> = Vec::new();
+        self.tree.hit(r, ray_t, |i, r, ray_t| {
+            let ball = &self.balls[i];
+            let oc = ball.center - r.origin();
+            let h = r.direction().dot(&oc);
+            let c = oc.length_squared() - ball.radius * ball.radius;
+            let discriminant = h * h - a * c;
+            if discriminant > 0.0 {
+                let sqrtd = discriminant.sqrt();
+                let t0 = ((h - sqrtd) / a).max(ray_t.min);
+                let t1 = ((h + sqrtd) / a).min(ray_t.max);
+                if t0 < t1 {
+                    candidates.push((i, t0, t1));
+                }
+            }
+            None
+        });
+        if candidates.is_empty() {
+            return None;
+        }
+
+        // The set of balls affecting the ray only changes where it enters or leaves one
+        let mut events: Vec<f64> = candidates
+            .iter()
+            .flat_map(|&(_, t0, t1)| [t0, t1])
+            .collect();
+        events.sort_by(f64::total_cmp);
+        events.dedup();
+
+        for span in events.windows(2) {
+            let (start, end) = (span[0], span[1]);
+
+            // Sum the field along the span as a polynomial in the distance s = t - start, measured
+            // from the span's start to keep the coefficients well conditioned
+            let mut coefficients = [0.0; 7];
+            for &(i, t0, t1) in &candidates {
+                if t0 > start || t1 < end {
+                    continue;
+                }
+                let ball = &self.balls[i];
+                let r2 = ball.radius * ball.radius;
+                let offset = r.at(start) - ball.center;
+
+                // 1 - |offset + s d|^2 / r^2, cubed
+                let falloff = [
+                    -a / r2,
+                    -2.0 * offset.dot(&r.direction()) / r2,
+                    1.0 - offset.length_squared() / r2,
+                ];
+                let squared = multiply(&falloff, &falloff);
+                let cubed = multiply(&squared, &falloff);
+                for (sum, c) in coefficients.iter_mut().zip(cubed) {
+                    *sum += ball.weight * c;
+                }
+            }
+            coefficients[6] -= self.threshold;
+
+            let Some(s) = solve_polynomial_in(&coefficients, 0.0, end - start)
+                .into_iter()
+                .find(|&s| ray_t.surrounds(start + s))
+            else {
+                continue;
+            };
+
+            let t = start + s;
+            let p = r.at(t);
+            let gradient = self.gradient(&p, &candidates);
+            if gradient.length_squared() == 0.0 {
+                continue;
+            }
+
+            // The field decreases outward, against its gradient
+            let outward_normal = -gradient.unit_vector();
+            return Some(HitRecord::new(p, t, r, &outward_normal, &self.mat));
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounding_box()
+    }
+}
+
+// Product of two polynomials given from the highest power down
+fn multiply(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut product = vec![0.0; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            product[i + j] += x * y;
+        }
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn single_ball_hits_at_closed_form_distance() {
+        // (1 - d^2 / 4)^3 = 1/8 where d = sqrt(2)
+        let ball = Metaball::new(Point3::new(0.0, 0.0, 0.0), 2.0, 1.0);
+        let blob = Metaballs::new(vec![ball], 0.125, material());
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = blob.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - (5.0 - 2.0f64.sqrt())).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+        assert!(rec.front_face);
+
+        // From the inside, the ray leaves through the far side
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = blob.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 2.0f64.sqrt()).abs() < 1e-9);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn overlapping_balls_blend_without_a_seam() {
+        let balls = vec![
+            Metaball::new(Point3::new(-0.6, 0.0, 0.0), 1.0, 1.0),
+            Metaball::new(Point3::new(0.6, 0.0, 0.0), 1.0, 1.0),
+        ];
+        let blob = Metaballs::new(balls, 0.3, material());
+
+        // Sweep rays down across the neck where the two fields overlap
+        let mut previous: Option<f64> = None;
+        for k in 0..=100 {
+            let x = -0.5 + k as f64 / 100.0;
+            let r = Ray::new(Point3::new(x, 5.0, 0.1), Vec3::new(0.0, -1.0, 0.0));
+            let rec = blob
+                .hit(&r, Interval::new(0.0, f64::INFINITY))
+                .unwrap_or_else(|| panic!("ray at x = {} missed", x));
+
+            assert!((blob.field(&rec.p) - 0.3).abs() < 1e-9);
+            if let Some(t) = previous {
+                assert!((rec.t - t).abs() < 0.02, "jump at x = {}", x);
+            }
+            previous = Some(rec.t);
+        }
+
+        // Halfway between the balls the surface is level
+        let r = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = blob.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        let y = (1.0 - 0.15f64.cbrt() - 0.36).sqrt();
+        assert!((rec.t - (5.0 - y)).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_parameters() {
+        let ball = Metaball::new(Point3::new(0.0, 0.0, 0.0), 1.0, 1.0);
+
+        let error = Metaballs::try_new(vec![ball], 0.0, material()).err();
+        assert_eq!(
+            error.as_deref(),
+            Some("metaball threshold 0 must be positive")
+        );
+
+        let flat = Metaball::new(Point3::new(1.0, 0.0, 0.0), 0.0, 1.0);
+        let error = Metaballs::try_new(vec![ball, flat], 0.5, material()).err();
+        assert_eq!(
+            error.as_deref(),
+            Some("metaball 1 radius 0 must be positive")
+        );
+    }
+}
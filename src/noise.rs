@@ -0,0 +1,212 @@
+// Seeded procedural noise for textures. The same seed always gives the same noise, so renders
+// using it are repeatable.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::vec3::{Point3, Vec3};
+
+// Entries in the lattice tables; the noise repeats every this many units along each axis
+const POINT_COUNT: usize = 256;
+
+// Gradient noise after Ken Perlin: random unit gradients at the integer lattice points, blended
+// across each cell with Hermite smoothing and trilinear interpolation. Values lie roughly in
+// [-1, 1] and are zero at the lattice points.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| random_unit_vector(&mut rng))
+            .collect();
+        let perm = [0; 3].map(|_| permutation(&mut rng));
+
+        Perlin { gradients, perm }
+    }
+
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let cell = [0, 1, 2].map(|axis| p[axis].floor());
+        let local = Vec3::new(p.x() - cell[0], p.y() - cell[1], p.z() - cell[2]);
+        let cell = cell.map(|c| c as i64);
+
+        // Hermite smoothing of the position within the cell
+        let smooth = [0, 1, 2].map(|axis| local[axis] * local[axis] * (3.0 - 2.0 * local[axis]));
+
+        let mut accum = 0.0;
+        for corner in 0..8 {
+            let offset = [0, 1, 2].map(|axis| (corner >> axis) & 1);
+            let hash = (0..3).fold(0, |hash, axis| {
+                hash ^ self.perm[axis][lattice_index(cell[axis] + offset[axis] as i64)]
+            });
+
+            let mut weight = 1.0;
+            let mut from_corner = local;
+            for axis in 0..3 {
+                if offset[axis] == 1 {
+                    weight *= smooth[axis];
+                    from_corner[axis] -= 1.0;
+                } else {
+                    weight *= 1.0 - smooth[axis];
+                }
+            }
+            accum += weight * self.gradients[hash].dot(&from_corner);
+        }
+
+        accum
+    }
+
+    // Fractal Brownian motion: octaves of noise, each at twice the frequency and half the
+    // amplitude of the last, normalized to the range of a single octave
+    pub fn fbm(&self, p: &Point3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, |n| n)
+    }
+
+    // Like fbm but summing the magnitude of each octave, which gives billowy, creased patterns
+    // in [0, 1] for fire, smoke and marble veins
+    pub fn turbulence(&self, p: &Point3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, f64::abs)
+    }
+
+    fn octaves(&self, p: &Point3, octaves: u32, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut accum = 0.0;
+        let mut total_weight = 0.0;
+        let mut weight = 1.0;
+        let mut temp_p = *p;
+
+        for _ in 0..octaves.max(1) {
+            accum += weight * shape(self.noise(&temp_p));
+            total_weight += weight;
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+
+        accum / total_weight
+    }
+}
+
+// Cellular noise after Steven Worley: one randomly placed feature point in each unit cell of
+// the lattice. F1 and F2 are the distances from a point to the nearest and second nearest
+// feature points, which outline Voronoi cells.
+pub struct Worley {
+    points: Vec<Vec3>,
+    perm: Vec<usize>,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let points = (0..POINT_COUNT)
+            .map(|_| Vec3::new(rng.gen(), rng.gen(), rng.gen()))
+            .collect();
+
+        Worley {
+            points,
+            perm: permutation(&mut rng),
+        }
+    }
+
+    // Returns (F1, F2)
+    pub fn distances(&self, p: &Point3) -> (f64, f64) {
+        let cell = [0, 1, 2].map(|axis| p[axis].floor() as i64);
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+
+        // With one point per cell the nearest always lies in the surrounding 3x3x3 cells, and
+        // the second nearest nearly always does
+        for dk in -1..=1 {
+            for dj in -1..=1 {
+                for di in -1..=1 {
+                    let neighbor = [cell[0] + di, cell[1] + dj, cell[2] + dk];
+                    let hash = neighbor.iter().fold(0, |hash, &c| {
+                        self.perm[(hash + lattice_index(c)) % POINT_COUNT]
+                    });
+                    let feature =
+                        Vec3::new(neighbor[0] as f64, neighbor[1] as f64, neighbor[2] as f64)
+                            + self.points[hash];
+
+                    let distance = (feature - *p).length();
+                    if distance < f1 {
+                        f2 = f1;
+                        f1 = distance;
+                    } else if distance < f2 {
+                        f2 = distance;
+                    }
+                }
+            }
+        }
+
+        (f1, f2)
+    }
+}
+
+fn lattice_index(c: i64) -> usize {
+    c.rem_euclid(POINT_COUNT as i64) as usize
+}
+
+fn permutation(rng: &mut StdRng) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+    perm.shuffle(rng);
+    perm
+}
+
+fn random_unit_vector(rng: &mut StdRng) -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let lensq = p.length_squared();
+        if 1e-160 < lensq && lensq <= 1.0 {
+            return p / lensq.sqrt();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Point3> {
+        (0..200).map(|i| {
+            let t = i as f64;
+            Point3::new(0.37 * t - 20.0, 1.13 * t.sin() * 7.0, 0.011 * t * t)
+        })
+    }
+
+    #[test]
+    fn same_seed_gives_same_noise() {
+        let (a, b, c) = (Perlin::new(7), Perlin::new(7), Perlin::new(8));
+        let (wa, wb, wc) = (Worley::new(7), Worley::new(7), Worley::new(8));
+
+        let mut differs = false;
+        for p in sample_points() {
+            assert_eq!(a.noise(&p), b.noise(&p));
+            assert_eq!(a.turbulence(&p, 5), b.turbulence(&p, 5));
+            assert_eq!(wa.distances(&p), wb.distances(&p));
+            differs |= a.noise(&p) != c.noise(&p) && wa.distances(&p) != wc.distances(&p);
+        }
+        assert!(differs, "different seeds gave the same noise");
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let perlin = Perlin::new(1);
+        let worley = Worley::new(1);
+
+        for p in sample_points() {
+            assert!(perlin.noise(&p).abs() <= 1.0);
+            assert!(perlin.fbm(&p, 6).abs() <= 1.0);
+            assert!((0.0..=1.0).contains(&perlin.turbulence(&p, 6)));
+
+            let (f1, f2) = worley.distances(&p);
+            assert!(0.0 <= f1 && f1 <= f2 && f1 <= 3.0_f64.sqrt());
+        }
+
+        // Zero at the lattice points
+        assert_eq!(perlin.noise(&Point3::new(3.0, -2.0, 7.0)), 0.0);
+    }
+}
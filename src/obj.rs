@@ -0,0 +1,752 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    color::Color,
+    hittable_list::HittableList,
+    material::Material,
+    mesh::{MeshBuffers, TriangleMesh},
+    onb::Onb,
+    vec3::{Point3, Vec3},
+};
+
+// Material used for faces that appear before any usemtl statement
+fn default_material() -> Material {
+    Material::lambertian(Color::new(0.5, 0.5, 0.5))
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+// One triangle mesh per group and material combination found in the file
+pub struct ObjGroup {
+    pub name: String,
+    pub material_name: Option<String>,
+    pub mesh: TriangleMesh,
+}
+
+// Contents of a Wavefront OBJ file. All groups share the same vertex buffers.
+pub struct ObjScene {
+    pub groups: Vec<ObjGroup>,
+}
+
+impl ObjScene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let reader = open(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        ObjParser::new(path, base_dir).parse(reader)
+    }
+
+    pub fn into_hittable_list(self) -> HittableList {
+        let mut list = HittableList::new();
+        for group in self.groups {
+            list.add(Box::new(group.mesh));
+        }
+        list
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, ObjError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| ObjError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+// Location used when reporting parse errors
+struct Location<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl Location<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn parse_f64(&self, token: Option<&str>, what: &str) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("missing {}", what)))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} '{}'", what, token)))
+    }
+
+    fn parse_vec3(
+        &self,
+        tokens: &mut std::str::SplitWhitespace,
+        what: &str,
+    ) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.parse_f64(tokens.next(), what)?,
+            self.parse_f64(tokens.next(), what)?,
+            self.parse_f64(tokens.next(), what)?,
+        ))
+    }
+}
+
+// Vertex of a face, as indices into the position, texture coordinate and normal lists
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+// Group name and material name that faces are collected under
+type GroupKey = (String, Option<String>);
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    base_dir: &'a Path,
+
+    positions: Vec<Point3>,
+    uvs: Vec<[f64; 2]>,
+    normals: Vec<Vec3>,
+    materials: HashMap<String, Material>,
+
+    // Unified vertex buffers and the face vertex each entry was built from
+    buffers: MeshBuffers,
+    vertex_map: HashMap<FaceVertex, usize>,
+    has_normals: bool,
+    has_uvs: bool,
+
+    group: String,
+    material: Option<String>,
+    faces: Vec<(GroupKey, Vec<[usize; 3]>)>,
+    // Index into faces of each group, which keeps the groups in file order
+    group_index: HashMap<GroupKey, usize>,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(path: &'a Path, base_dir: &'a Path) -> Self {
+        ObjParser {
+            path,
+            base_dir,
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            materials: HashMap::new(),
+            buffers: MeshBuffers::default(),
+            vertex_map: HashMap::new(),
+            has_normals: false,
+            has_uvs: false,
+            group: String::from("default"),
+            material: None,
+            faces: Vec::new(),
+            group_index: HashMap::new(),
+        }
+    }
+
+    fn parse<R: BufRead>(mut self, reader: R) -> Result<ObjScene, ObjError> {
+        let mut line_count = 0;
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|source| ObjError::Io {
+                path: self.path.to_path_buf(),
+                source,
+            })?;
+            let loc = Location {
+                path: self.path,
+                line: index + 1,
+            };
+            self.parse_line(&loc, &line)?;
+            line_count = index + 1;
+        }
+
+        let loc = Location {
+            path: self.path,
+            line: line_count,
+        };
+        self.finish(&loc)
+    }
+
+    fn parse_line(&mut self, loc: &Location, line: &str) -> Result<(), ObjError> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+
+        match keyword {
+            "v" => {
+                let p = loc.parse_vec3(&mut tokens, "vertex coordinate")?;
+                self.positions.push(p);
+            }
+            "vn" => {
+                let n = loc.parse_vec3(&mut tokens, "normal coordinate")?;
+                self.normals.push(n);
+            }
+            "vt" => {
+                let u = loc.parse_f64(tokens.next(), "texture coordinate")?;
+                let v = match tokens.next() {
+                    Some(token) => loc.parse_f64(Some(token), "texture coordinate")?,
+                    None => 0.0,
+                };
+                self.uvs.push([u, v]);
+            }
+            "f" => {
+                let vertices = tokens
+                    .map(|token| self.parse_face_vertex(loc, token))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if vertices.len() < 3 {
+                    return Err(loc.error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        vertices.len()
+                    )));
+                }
+                self.add_face(&vertices);
+            }
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                self.group = if name.is_empty() {
+                    String::from("default")
+                } else {
+                    name
+                };
+            }
+            "usemtl" => {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| loc.error("usemtl without material name"))?;
+                if !self.materials.contains_key(name) {
+                    return Err(loc.error(format!("undefined material '{}'", name)));
+                }
+                self.material = Some(name.to_string());
+            }
+            "mtllib" => {
+                for name in tokens {
+                    let mtl_path = self.base_dir.join(name);
+                    let materials = load_mtl(&mtl_path)?;
+                    self.materials.extend(materials);
+                }
+            }
+            // Smoothing groups, lines, points, free-form geometry and display attributes are
+            // not rendered
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn parse_face_vertex(&self, loc: &Location, token: &str) -> Result<FaceVertex, ObjError> {
+        let mut parts = token.split('/');
+
+        let v = parts.next().unwrap_or("");
+        let vt = parts.next().filter(|s| !s.is_empty());
+        let vn = parts.next().filter(|s| !s.is_empty());
+        if parts.next().is_some() {
+            return Err(loc.error(format!("invalid face vertex '{}'", token)));
+        }
+
+        let v = resolve_index(loc, v, self.positions.len(), "vertex")?;
+        let vt = vt
+            .map(|vt| resolve_index(loc, vt, self.uvs.len(), "texture coordinate"))
+            .transpose()?;
+        let vn = vn
+            .map(|vn| resolve_index(loc, vn, self.normals.len(), "normal"))
+            .transpose()?;
+
+        Ok((v, vt, vn))
+    }
+
+    fn add_face(&mut self, vertices: &[FaceVertex]) {
+        let points: Vec<Point3> = vertices
+            .iter()
+            .map(|&(v, _, _)| self.positions[v])
+            .collect();
+
+        // Area-weighted normal of the whole polygon, so a collinear first corner doesn't matter.
+        // Faces without any area are skipped, as TriangleMesh would never hit them anyway.
+        let normal = (1..points.len() - 1)
+            .map(|i| (points[i] - points[0]).cross(&(points[i + 1] - points[0])))
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, n| sum + n);
+        if normal.length_squared() == 0.0 {
+            return;
+        }
+        let face_normal = normal.unit_vector();
+
+        let indices: Vec<usize> = vertices
+            .iter()
+            .map(|&vertex| self.unified_vertex(vertex, face_normal))
+            .collect();
+
+        let key = (self.group.clone(), self.material.clone());
+        let faces = &mut self.faces;
+        let group = *self.group_index.entry(key.clone()).or_insert_with(|| {
+            faces.push((key, Vec::new()));
+            faces.len() - 1
+        });
+        let triangles = &mut self.faces[group].1;
+
+        for [a, b, c] in triangulate(&points, &face_normal) {
+            triangles.push([indices[a], indices[b], indices[c]]);
+        }
+    }
+
+    // Map an OBJ face vertex onto a single index into the unified mesh buffers. Vertices
+    // without a normal take the normal of the first face that uses them.
+    fn unified_vertex(&mut self, vertex: FaceVertex, face_normal: Vec3) -> usize {
+        if let Some(&index) = self.vertex_map.get(&vertex) {
+            return index;
+        }
+
+        let (v, vt, vn) = vertex;
+        let index = self.buffers.positions.len();
+        self.buffers.positions.push(self.positions[v]);
+        self.buffers
+            .normals
+            .push(vn.map_or(face_normal, |vn| self.normals[vn]));
+        self.buffers
+            .uvs
+            .push(vt.map_or([0.0, 0.0], |vt| self.uvs[vt]));
+        self.has_normals |= vn.is_some();
+        self.has_uvs |= vt.is_some();
+
+        self.vertex_map.insert(vertex, index);
+        index
+    }
+
+    fn finish(mut self, loc: &Location) -> Result<ObjScene, ObjError> {
+        if !self.has_normals {
+            self.buffers.normals.clear();
+        }
+        if !self.has_uvs {
+            self.buffers.uvs.clear();
+        }
+
+        let buffers = Arc::new(self.buffers);
+        let materials = self.materials;
+        let groups = self
+            .faces
+            .into_iter()
+            .map(|((name, material_name), triangles)| {
+                let mat = material_name
+                    .as_ref()
+                    .map_or_else(default_material, |m| materials[m].clone());
+                Ok(ObjGroup {
+                    name,
+                    material_name,
+                    mesh: TriangleMesh::try_new(buffers.clone(), triangles, mat)
+                        .map_err(|message| loc.error(message))?,
+                })
+            })
+            .collect::<Result<_, ObjError>>()?;
+
+        Ok(ObjScene { groups })
+    }
+}
+
+// Split a polygon into triangles by ear clipping, which unlike a fan also covers concave
+// polygons exactly. The polygon is projected onto the plane perpendicular to its normal, and
+// the triangles are returned as indices into points, wound the same way as the polygon.
+fn triangulate(points: &[Point3], normal: &Vec3) -> Vec<[usize; 3]> {
+    let onb = Onb::new(normal);
+    let flat: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| {
+            let d = *p - points[0];
+            (d.dot(&onb.u()), d.dot(&onb.v()))
+        })
+        .collect();
+
+    // Twice the signed area of the triangle abc in the plane
+    let area = |a: usize, b: usize, c: usize| {
+        let (ax, ay) = flat[a];
+        let (bx, by) = flat[b];
+        let (cx, cy) = flat[c];
+        (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
+    };
+    // Sign that makes corners turning the same way as the whole polygon positive
+    let orientation = if (1..points.len() - 1)
+        .map(|i| area(0, i, i + 1))
+        .sum::<f64>()
+        < 0.0
+    {
+        -1.0
+    } else {
+        1.0
+    };
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            )
+        };
+
+        // An ear is a convex corner whose triangle holds none of the other vertices
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = corner(i);
+            orientation * area(a, b, c) > 0.0
+                && remaining.iter().all(|&j| {
+                    j == a
+                        || j == b
+                        || j == c
+                        || orientation * area(a, b, j) < 0.0
+                        || orientation * area(b, c, j) < 0.0
+                        || orientation * area(c, a, j) < 0.0
+                })
+        });
+
+        // Self-intersecting polygons can run out of ears; the rest then becomes a fan
+        let Some(i) = ear else {
+            break;
+        };
+        let (a, b, c) = corner(i);
+        triangles.push([a, b, c]);
+        remaining.remove(i);
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+// Resolve a 1-based (or negative, relative to the end) OBJ index into a 0-based one
+fn resolve_index(loc: &Location, token: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| loc.error(format!("invalid {} index '{}'", what, token)))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(loc.error(format!(
+            "{} index {} out of range ({} defined)",
+            what, index, count
+        )));
+    }
+
+    Ok(resolved as usize)
+}
+
+// Material parameters as written in an MTL file, before mapping onto a Material
+struct MtlEntry {
+    kd: Color,
+    ks: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u32,
+}
+
+impl Default for MtlEntry {
+    fn default() -> Self {
+        MtlEntry {
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.0,
+            d: 1.0,
+            illum: 1,
+        }
+    }
+}
+
+impl MtlEntry {
+    // Map the MTL illumination model onto the closest Material variant:
+    // - transparent (d < 1) or refractive illumination models (4, 6, 7, 9) become Dielectric
+    // - ray traced reflection models (3, 5, 8) become Metal, with Ns controlling the fuzz
+    // - everything else becomes Lambertian with the diffuse color
+    fn to_material(&self) -> Material {
+        if self.d < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            let refraction_index = if self.ni > 1.0 { self.ni } else { 1.5 };
+            Material::Dielectric { refraction_index }
+        } else if matches!(self.illum, 3 | 5 | 8) {
+            let fuzz = (1.0 - self.ns / 1000.0).clamp(0.0, 1.0);
+            Material::metal(self.ks, fuzz)
+        } else {
+            Material::lambertian(self.kd)
+        }
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let reader = open(path)?;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|source| ObjError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let loc = Location {
+            path,
+            line: index + 1,
+        };
+
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| loc.error("newmtl without material name"))?;
+            if let Some((name, entry)) = current.take() {
+                materials.insert(name, entry.to_material());
+            }
+            current = Some((name.to_string(), MtlEntry::default()));
+            continue;
+        }
+
+        let Some((_, entry)) = current.as_mut() else {
+            return Err(loc.error(format!("'{}' before any newmtl statement", keyword)));
+        };
+
+        match keyword {
+            "Kd" => entry.kd = loc.parse_vec3(&mut tokens, "Kd component")?,
+            "Ks" => entry.ks = loc.parse_vec3(&mut tokens, "Ks component")?,
+            "Ns" => entry.ns = loc.parse_f64(tokens.next(), "Ns value")?,
+            "Ni" => entry.ni = loc.parse_f64(tokens.next(), "Ni value")?,
+            "d" => entry.d = loc.parse_f64(tokens.next(), "d value")?,
+            "Tr" => entry.d = 1.0 - loc.parse_f64(tokens.next(), "Tr value")?,
+            "illum" => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| loc.error("missing illum value"))?;
+                entry.illum = token
+                    .parse()
+                    .map_err(|_| loc.error(format!("invalid illum value '{}'", token)))?;
+            }
+            // Ambient, emissive and texture map statements have no equivalent yet
+            _ => {}
+        }
+    }
+
+    if let Some((name, entry)) = current.take() {
+        materials.insert(name, entry.to_material());
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{hittable::Hittable, interval::Interval, ray::Ray};
+
+    fn parse_in(base_dir: &Path, text: &str) -> Result<ObjScene, ObjError> {
+        ObjParser::new(Path::new("test.obj"), base_dir).parse(text.as_bytes())
+    }
+
+    fn parse(text: &str) -> Result<ObjScene, ObjError> {
+        parse_in(Path::new(""), text)
+    }
+
+    // Whether a ray straight down onto (x, y) hits the mesh
+    fn hits_at(mesh: &TriangleMesh, x: f64, y: f64) -> bool {
+        let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        mesh.hit(&r, Interval::new(0.0, f64::INFINITY)).is_some()
+    }
+
+    #[test]
+    fn triangulates_concave_polygons() {
+        // U shape whose first vertex sits at the bottom of the notch, where a fan would cover
+        // the notch and leave part of the polygon uncovered
+        let scene = parse(
+            "v 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nv 0 0 0\nv 3 0 0\nv 3 2 0\nv 2 2 0\n\
+             f 1 2 3 4 5 6 7 8\n",
+        )
+        .unwrap();
+        let mesh = &scene.groups[0].mesh;
+        assert_eq!(mesh.triangle_count(), 6);
+
+        assert!(!hits_at(mesh, 1.2, 1.5));
+        for (x, y) in [(0.5, 1.5), (2.5, 1.5), (1.5, 0.5), (0.1, 0.1), (2.9, 1.9)] {
+            assert!(hits_at(mesh, x, y), "missed ({}, {})", x, y);
+        }
+        for n in &mesh.buffers().normals {
+            assert!((*n - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn handles_collinear_and_degenerate_faces() {
+        // The first three vertices of the quad are collinear
+        let scene =
+            parse("v 0 0 0\nv 1 0 0\nv 2 0 0\nv 2 1 0\nv 0 1 0\nf 1 2 3 4 5\nf 1 2 3\n").unwrap();
+        let mesh = &scene.groups[0].mesh;
+
+        // The collinear triangle has no area and is skipped
+        assert_eq!(mesh.triangle_count(), 3);
+        assert!(hits_at(mesh, 1.0, 0.5));
+        for n in &mesh.buffers().normals {
+            assert!((*n - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn resolves_relative_indices() {
+        let scene = parse(
+            "v 5 5 5\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n\
+             f -3/-3/-1 -2/-2/-1 -1/-1/-1\nf 2/1/1 3/2/1 4/3/1\nf 2//1 3//1 4//1\n",
+        )
+        .unwrap();
+        let mesh = &scene.groups[0].mesh;
+        let buffers = mesh.buffers();
+
+        assert_eq!(mesh.triangle_count(), 3);
+        // Relative and absolute references to the same vertices share them
+        assert_eq!(buffers.positions.len(), 6);
+        let expected = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        for (p, e) in buffers.positions.iter().zip(expected) {
+            assert!((*p - e).length() < 1e-12);
+        }
+        assert_eq!(buffers.uvs[1], [1.0, 0.0]);
+    }
+
+    #[test]
+    fn maps_usemtl_onto_mtl_materials() {
+        let dir = std::env::temp_dir().join(format!("raytracer-obj-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("scene.mtl"),
+            "newmtl red\nKd 1 0 0\n\nnewmtl mirror\nillum 3\nKs 0.9 0.9 0.9\nNs 900\n\n\
+             newmtl glass\nd 0.5\nNi 1.3\n",
+        )
+        .unwrap();
+
+        let materials = load_mtl(&dir.join("scene.mtl")).unwrap();
+        assert!(matches!(materials["red"], Material::Lambertian { .. }));
+        assert!(matches!(materials["mirror"], Material::Metal { .. }));
+        assert!(matches!(
+            materials["glass"],
+            Material::Dielectric { refraction_index } if refraction_index == 1.3
+        ));
+
+        let scene = parse_in(
+            &dir,
+            "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             f 1 2 3\nusemtl red\nf 1 2 3\nusemtl glass\nf 1 2 3\ng lid\nusemtl red\nf 1 2 3\n\
+             g\nusemtl red\nf 1 3 2\n",
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let groups: Vec<_> = scene
+            .unwrap()
+            .groups
+            .iter()
+            .map(|g| {
+                (
+                    g.name.clone(),
+                    g.material_name.clone(),
+                    g.mesh.triangle_count(),
+                )
+            })
+            .collect();
+        let group = |name: &str, material: Option<&str>, count| {
+            (name.to_string(), material.map(String::from), count)
+        };
+        assert_eq!(
+            groups,
+            [
+                group("default", None, 1),
+                group("default", Some("red"), 2),
+                group("default", Some("glass"), 1),
+                group("lid", Some("red"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let cases = [
+            ("v 1 2\n", 1, "missing vertex coordinate"),
+            ("v 0 0 0\nv 1 0 x\n", 2, "invalid vertex coordinate 'x'"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n", 5, "out of range"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n", 4, "out of range"),
+            (
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\n# comment\nf -4 1 2\n",
+                5,
+                "out of range",
+            ),
+            ("v 0 0 0\nv 1 0 0\nf 1 2\n", 3, "at least 3 vertices"),
+            ("v 0 0 0\nf 1/1/1/1 1 1\n", 2, "invalid face vertex"),
+            (
+                "v 0 0 0\nf 1/1 1 1\n",
+                2,
+                "texture coordinate index 1 out of range",
+            ),
+            ("vt 0.5 y\n", 1, "invalid texture coordinate 'y'"),
+            ("\n\nusemtl missing\n", 3, "undefined material 'missing'"),
+            ("usemtl\n", 1, "usemtl without material name"),
+        ];
+
+        for (text, expected_line, expected_message) in cases {
+            match parse(text) {
+                Err(ObjError::Parse { line, message, .. }) => {
+                    assert_eq!(line, expected_line, "{:?}: {}", text, message);
+                    assert!(
+                        message.contains(expected_message),
+                        "{:?}: {}",
+                        text,
+                        message
+                    );
+                }
+                Err(e) => panic!("{:?}: unexpected error {}", text, e),
+                Ok(_) => panic!("{:?} parsed without error", text),
+            }
+        }
+    }
+}
@@ -0,0 +1,686 @@
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::SplitAsciiWhitespace,
+    sync::Arc,
+};
+
+use crate::{
+    color::Color,
+    material::Material,
+    mesh::{MeshBuffers, TriangleMesh},
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlyError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            PlyError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for PlyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PlyError::Io { source, .. } => Some(source),
+            PlyError::Format { .. } => None,
+        }
+    }
+}
+
+// Load a PLY file (ASCII, binary little endian or binary big endian) as a triangle mesh. Vertex
+// positions are required; normals, colors and texture coordinates are loaded when present.
+// Polygon faces are triangulated as fans. Give the material a VertexColorTexture to show the
+// colors.
+pub fn load_ply<P: AsRef<Path>>(path: P, mat: Material) -> Result<TriangleMesh, PlyError> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|source| PlyError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let format_error = |message| PlyError::Format {
+        path: path.to_path_buf(),
+        message,
+    };
+    let (buffers, triangles) = parse_ply(&data).map_err(format_error)?;
+    TriangleMesh::try_new(Arc::new(buffers), triangles, mat).map_err(format_error)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::Int8),
+            "uchar" | "uint8" => Ok(ScalarType::UInt8),
+            "short" | "int16" => Ok(ScalarType::Int16),
+            "ushort" | "uint16" => Ok(ScalarType::UInt16),
+            "int" | "int32" => Ok(ScalarType::Int32),
+            "uint" | "uint32" => Ok(ScalarType::UInt32),
+            "float" | "float32" => Ok(ScalarType::Float32),
+            "double" | "float64" => Ok(ScalarType::Float64),
+            _ => Err(format!("unknown property type '{}'", name)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // Scale that maps integer color channels onto [0, 1]
+    fn color_scale(&self) -> f64 {
+        match self {
+            ScalarType::UInt8 => 1.0 / 255.0,
+            ScalarType::UInt16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar {
+        name: String,
+        ty: ScalarType,
+    },
+    List {
+        name: String,
+        count_ty: ScalarType,
+        item_ty: ScalarType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name()))
+    }
+}
+
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+}
+
+// Split the header off the file contents and parse it
+fn parse_header(data: &[u8]) -> Result<(Header, &[u8]), String> {
+    const END_HEADER: &[u8] = b"end_header";
+
+    let end = data
+        .windows(END_HEADER.len())
+        .position(|w| w == END_HEADER)
+        .ok_or("missing end_header")?;
+    let mut body_start = end + END_HEADER.len();
+    if data.get(body_start) == Some(&b'\r') {
+        body_start += 1;
+    }
+    if data.get(body_start) == Some(&b'\n') {
+        body_start += 1;
+    }
+
+    let text = std::str::from_utf8(&data[..end]).map_err(|_| "header is not valid text")?;
+    let mut lines = text.lines().enumerate();
+
+    match lines.next() {
+        Some((_, line)) if line.trim() == "ply" => {}
+        _ => return Err(String::from("not a PLY file (missing 'ply' magic)")),
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for (index, line) in lines {
+        let error = |message: String| format!("header line {}: {}", index + 1, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", format, version] => {
+                if *version != "1.0" {
+                    return Err(error(format!("unsupported version '{}'", version)));
+                }
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::BinaryLittleEndian,
+                    "binary_big_endian" => Encoding::BinaryBigEndian,
+                    _ => return Err(error(format!("unknown format '{}'", format))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| error(format!("invalid element count '{}'", count)))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", "list", count_ty, item_ty, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error(String::from("property before any element")))?;
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count_ty: ScalarType::parse(count_ty).map_err(error)?,
+                    item_ty: ScalarType::parse(item_ty).map_err(error)?,
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error(String::from("property before any element")))?;
+                element.properties.push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: ScalarType::parse(ty).map_err(error)?,
+                });
+            }
+            _ => return Err(error(format!("malformed header line '{}'", line))),
+        }
+    }
+
+    let encoding = encoding.ok_or("missing format line")?;
+    Ok((Header { encoding, elements }, &data[body_start..]))
+}
+
+// Reads property values from the body in either encoding
+enum BodyReader<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary {
+        data: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> BodyReader<'a> {
+    fn new(encoding: Encoding, body: &'a [u8]) -> Result<Self, String> {
+        Ok(match encoding {
+            Encoding::Ascii => {
+                let text = std::str::from_utf8(body).map_err(|_| "body is not valid text")?;
+                BodyReader::Ascii(text.split_ascii_whitespace())
+            }
+            Encoding::BinaryLittleEndian => BodyReader::Binary {
+                data: body,
+                pos: 0,
+                big_endian: false,
+            },
+            Encoding::BinaryBigEndian => BodyReader::Binary {
+                data: body,
+                pos: 0,
+                big_endian: true,
+            },
+        })
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        match self {
+            BodyReader::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of data")?;
+                token
+                    .parse()
+                    .map_err(|_| format!("invalid number '{}'", token))
+            }
+            BodyReader::Binary {
+                data,
+                pos,
+                big_endian,
+            } => {
+                let size = ty.size();
+                let bytes = data
+                    .get(*pos..*pos + size)
+                    .ok_or("unexpected end of data")?;
+                *pos += size;
+
+                let mut buf = [0u8; 8];
+                buf[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+
+                Ok(match ty {
+                    ScalarType::Int8 => i8::from_le_bytes([buf[0]]) as f64,
+                    ScalarType::UInt8 => buf[0] as f64,
+                    ScalarType::Int16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    ScalarType::UInt16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    ScalarType::Int32 => {
+                        i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64
+                    }
+                    ScalarType::UInt32 => {
+                        u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64
+                    }
+                    ScalarType::Float32 => {
+                        f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64
+                    }
+                    ScalarType::Float64 => f64::from_le_bytes(buf),
+                })
+            }
+        }
+    }
+
+    fn read_count(&mut self, ty: ScalarType) -> Result<usize, String> {
+        let count = self.read(ty)?;
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(format!("invalid list length {}", count));
+        }
+        Ok(count as usize)
+    }
+}
+
+// Indices of the vertex properties we know how to use
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    color: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Result<Self, String> {
+        let find3 = |names: [&[&str]; 3]| -> Option<[usize; 3]> {
+            Some([
+                element.find(names[0])?,
+                element.find(names[1])?,
+                element.find(names[2])?,
+            ])
+        };
+
+        let position = find3([&["x"], &["y"], &["z"]])
+            .ok_or("vertex element is missing x, y or z property")?;
+        let normal = find3([&["nx"], &["ny"], &["nz"]]);
+        let color = find3([
+            &["red", "r", "diffuse_red"],
+            &["green", "g", "diffuse_green"],
+            &["blue", "b", "diffuse_blue"],
+        ]);
+        let uv = match (
+            element.find(&["u", "s", "texture_u", "texture_s"]),
+            element.find(&["v", "t", "texture_v", "texture_t"]),
+        ) {
+            (Some(u), Some(v)) => Some([u, v]),
+            _ => None,
+        };
+
+        for index in position
+            .iter()
+            .chain(normal.iter().flatten())
+            .chain(color.iter().flatten())
+            .chain(uv.iter().flatten())
+        {
+            if let Property::List { name, .. } = &element.properties[*index] {
+                return Err(format!("vertex property '{}' must not be a list", name));
+            }
+        }
+
+        Ok(VertexLayout {
+            position,
+            normal,
+            color,
+            uv,
+        })
+    }
+}
+
+fn parse_ply(data: &[u8]) -> Result<(MeshBuffers, Vec<[usize; 3]>), String> {
+    let (header, body) = parse_header(data)?;
+    let mut reader = BodyReader::new(header.encoding, body)?;
+
+    let mut buffers = MeshBuffers::default();
+    let mut triangles = Vec::new();
+    let mut found_vertices = false;
+
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                found_vertices = true;
+                read_vertices(&mut reader, element, &mut buffers)
+                    .map_err(|message| format!("vertex data: {}", message))?;
+            }
+            "face" => {
+                read_faces(&mut reader, element, &mut triangles)
+                    .map_err(|message| format!("face data: {}", message))?;
+            }
+            _ => skip_element(&mut reader, element)
+                .map_err(|message| format!("{} data: {}", element.name, message))?,
+        }
+    }
+
+    if !found_vertices {
+        return Err(String::from("no vertex element"));
+    }
+
+    let vertex_count = buffers.positions.len();
+    if let Some(index) = triangles.iter().flatten().find(|&&i| i >= vertex_count) {
+        return Err(format!(
+            "face vertex index {} out of range ({} vertices)",
+            index, vertex_count
+        ));
+    }
+
+    Ok((buffers, triangles))
+}
+
+fn read_vertices(
+    reader: &mut BodyReader,
+    element: &Element,
+    buffers: &mut MeshBuffers,
+) -> Result<(), String> {
+    let layout = VertexLayout::new(element)?;
+    let color_scale = match layout.color.map(|[r, _, _]| &element.properties[r]) {
+        Some(Property::Scalar { ty, .. }) => ty.color_scale(),
+        _ => 1.0,
+    };
+
+    let mut values = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            match property {
+                Property::Scalar { ty, .. } => *value = reader.read(*ty)?,
+                Property::List {
+                    count_ty, item_ty, ..
+                } => {
+                    for _ in 0..reader.read_count(*count_ty)? {
+                        reader.read(*item_ty)?;
+                    }
+                }
+            }
+        }
+
+        let [x, y, z] = layout.position;
+        buffers
+            .positions
+            .push(Point3::new(values[x], values[y], values[z]));
+
+        if let Some([x, y, z]) = layout.normal {
+            buffers
+                .normals
+                .push(Vec3::new(values[x], values[y], values[z]));
+        }
+        if let Some([r, g, b]) = layout.color {
+            buffers
+                .colors
+                .push(color_scale * Color::new(values[r], values[g], values[b]));
+        }
+        if let Some([u, v]) = layout.uv {
+            buffers.uvs.push([values[u], values[v]]);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(
+    reader: &mut BodyReader,
+    element: &Element,
+    triangles: &mut Vec<[usize; 3]>,
+) -> Result<(), String> {
+    let index_property = element
+        .find(&["vertex_indices", "vertex_index"])
+        .ok_or("face element has no vertex_indices property")?;
+    if let Property::Scalar { name, .. } = &element.properties[index_property] {
+        return Err(format!("face property '{}' must be a list", name));
+    }
+
+    let mut polygon = Vec::new();
+    for _ in 0..element.count {
+        for (i, property) in element.properties.iter().enumerate() {
+            match property {
+                Property::Scalar { ty, .. } => {
+                    reader.read(*ty)?;
+                }
+                Property::List {
+                    count_ty, item_ty, ..
+                } => {
+                    let count = reader.read_count(*count_ty)?;
+                    polygon.clear();
+                    for _ in 0..count {
+                        let index = reader.read(*item_ty)?;
+                        if i == index_property {
+                            if index < 0.0 {
+                                return Err(format!("negative vertex index {}", index));
+                            }
+                            polygon.push(index as usize);
+                        }
+                    }
+
+                    if i == index_property {
+                        if count < 3 {
+                            return Err(format!("face with only {} vertices", count));
+                        }
+                        for k in 1..polygon.len() - 1 {
+                            triangles.push([polygon[0], polygon[k], polygon[k + 1]]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_element(reader: &mut BodyReader, element: &Element) -> Result<(), String> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::Scalar { ty, .. } => {
+                    reader.read(*ty)?;
+                }
+                Property::List {
+                    count_ty, item_ty, ..
+                } => {
+                    for _ in 0..reader.read_count(*count_ty)? {
+                        reader.read(*item_ty)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color,
+        hittable::Hittable,
+        interval::Interval,
+        ray::Ray,
+        texture::{Texture, VertexColorTexture},
+        vec3::{Point3, Vec3},
+    };
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    // Unit square in the xy plane with a color per corner, as a single quad face
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+
+    fn encode(encoding: Encoding) -> Vec<u8> {
+        let format = match encoding {
+            Encoding::Ascii => "ascii",
+            Encoding::BinaryLittleEndian => "binary_little_endian",
+            Encoding::BinaryBigEndian => "binary_big_endian",
+        };
+        let mut data = format!(
+            "ply\nformat {} 1.0\ncomment test square\n{}",
+            format, HEADER
+        )
+        .into_bytes();
+
+        let float = |data: &mut Vec<u8>, value: f32| match encoding {
+            Encoding::Ascii => data.extend_from_slice(format!("{} ", value).as_bytes()),
+            Encoding::BinaryLittleEndian => data.extend_from_slice(&value.to_le_bytes()),
+            Encoding::BinaryBigEndian => data.extend_from_slice(&value.to_be_bytes()),
+        };
+        let int = |data: &mut Vec<u8>, value: i32| match encoding {
+            Encoding::Ascii => data.extend_from_slice(format!("{} ", value).as_bytes()),
+            Encoding::BinaryLittleEndian => data.extend_from_slice(&value.to_le_bytes()),
+            Encoding::BinaryBigEndian => data.extend_from_slice(&value.to_be_bytes()),
+        };
+        let byte = |data: &mut Vec<u8>, value: u8| match encoding {
+            Encoding::Ascii => data.extend_from_slice(format!("{} ", value).as_bytes()),
+            _ => data.push(value),
+        };
+
+        for (p, c) in POSITIONS.iter().zip(COLORS) {
+            for &x in p.iter().chain(&[0.0, 0.0, 1.0]) {
+                float(&mut data, x);
+            }
+            for x in c {
+                byte(&mut data, x);
+            }
+            float(&mut data, p[0]);
+            float(&mut data, p[1]);
+            if encoding == Encoding::Ascii {
+                data.push(b'\n');
+            }
+        }
+        byte(&mut data, 4);
+        for i in 0..4 {
+            int(&mut data, i);
+        }
+        data
+    }
+
+    #[test]
+    fn reads_every_encoding() {
+        for encoding in [
+            Encoding::Ascii,
+            Encoding::BinaryLittleEndian,
+            Encoding::BinaryBigEndian,
+        ] {
+            let (buffers, triangles) = parse_ply(&encode(encoding)).unwrap();
+
+            assert_eq!(triangles, [[0, 1, 2], [0, 2, 3]], "{:?}", encoding);
+            assert_eq!(buffers.positions.len(), 4);
+            for (i, p) in POSITIONS.iter().enumerate() {
+                let expected = Point3::new(p[0] as f64, p[1] as f64, p[2] as f64);
+                assert!((buffers.positions[i] - expected).length() < 1e-12);
+                assert!((buffers.normals[i] - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+                assert_eq!(buffers.uvs[i], [p[0] as f64, p[1] as f64]);
+
+                let c = COLORS[i].map(|c| c as f64 / 255.0);
+                assert!((buffers.colors[i] - Color::new(c[0], c[1], c[2])).length() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn vertex_colors_reach_the_texture() {
+        let (buffers, triangles) = parse_ply(&encode(Encoding::BinaryLittleEndian)).unwrap();
+        let mat = Material::Lambertian {
+            albedo: Arc::new(VertexColorTexture::new()),
+        };
+        let mesh = TriangleMesh::new(Arc::new(buffers), triangles, mat);
+
+        // Halfway along the edge from the red corner to the green one
+        let r = Ray::new(Point3::new(0.5, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        let color = VertexColorTexture::new().value_at(&rec);
+        assert!((color - Color::new(0.5, 0.5, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let valid = String::from_utf8(encode(Encoding::Ascii)).unwrap();
+        let cases = [
+            (
+                valid.replace(
+                    "property list uchar int vertex_indices",
+                    "property int vertex_indices",
+                ),
+                "must be a list",
+            ),
+            (
+                valid.replace("format ascii", "format ebcdic"),
+                "unknown format",
+            ),
+            (valid.replace("property float z\n", ""), "missing x, y or z"),
+            (
+                valid.replace("4 0 1 2 3", "4 0 1 2 7"),
+                "index 7 out of range",
+            ),
+            (valid.replace("4 0 1 2 3", "2 0 1"), "only 2 vertices"),
+            (
+                valid[..valid.len() - 4].to_string(),
+                "unexpected end of data",
+            ),
+        ];
+
+        for (text, expected) in cases {
+            let error = parse_ply(text.as_bytes()).expect_err("malformed file parsed");
+            assert!(error.contains(expected), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use rand::Rng;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    interval::Interval,
+    light::Light,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+// Parallelogram spanned by the edge vectors u and v from the corner q
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3, // constant for finding the planar hit point coordinates
+    mat: Material,
+    bbox: Aabb,
+    normal: Vec3,
+    d: f64, // plane equation: normal . p = d
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Material) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit_vector();
+
+        // Compute the bounding box of all four vertices
+        let bbox_diagonal1 = Aabb::from_points(q, q + u + v);
+        let bbox_diagonal2 = Aabb::from_points(q + u, q + v);
+
+        Quad {
+            q,
+            u,
+            v,
+            w: n / n.dot(&n),
+            mat,
+            bbox: Aabb::surrounding(&bbox_diagonal1, &bbox_diagonal2),
+            normal,
+            d: normal.dot(&q),
+            area: n.length(),
+        }
+    }
+
+    // Given the hit point in plane coordinates, return None if it is outside the primitive,
+    // otherwise the (u, v) coordinates of the hit
+    fn is_interior(a: f64, b: f64) -> Option<(f64, f64)> {
+        let unit_interval = Interval::new(0.0, 1.0);
+
+        if !unit_interval.contains(a) || !unit_interval.contains(b) {
+            return None;
+        }
+
+        Some((a, b))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(&r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        // Return None if the hit point parameter t is outside the ray interval
+        let t = (self.d - self.normal.dot(&r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = self.w.dot(&planar_hitpt_vector.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar_hitpt_vector));
+
+        let (u, v) = Self::is_interior(alpha, beta)?;
+
+        let mut rec = HitRecord::new(intersection, t, r, &self.normal, &self.mat);
+        rec.u = u;
+        rec.v = v;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Light>) {
+        if self.mat.is_emissive() && self.area > 0.0 {
+            lights.push(self);
+        }
+    }
+}
+
+impl Light for Quad {
+    // Points are sampled uniformly over the area, which seen from origin has a density per
+    // solid angle of distance^2 / (cosine * area)
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        let Some(rec) = self.hit(
+            &Ray::with_time(*origin, *direction, time),
+            Interval::new(0.001, f64::INFINITY),
+        ) else {
+            return 0.0;
+        };
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(&rec.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, _time: f64) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let p = self.q + (rng.gen::<f64>() * self.u) + (rng.gen::<f64>() * self.v);
+        p - *origin
+    }
+}
+
+// Returns the 3D box (six sides) that contains the two opposite vertices a and b
+pub fn make_box(a: Point3, b: Point3, mat: Material) -> HittableList {
+    let mut sides = HittableList::new();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    sides.add(Box::new(Quad::new(
+        Point3::new(min.x(), min.y(), max.z()),
+        dx,
+        dy,
+        mat.clone(),
+    ))); // front
+    sides.add(Box::new(Quad::new(
+        Point3::new(max.x(), min.y(), max.z()),
+        -dz,
+        dy,
+        mat.clone(),
+    ))); // right
+    sides.add(Box::new(Quad::new(
+        Point3::new(max.x(), min.y(), min.z()),
+        -dx,
+        dy,
+        mat.clone(),
+    ))); // back
+    sides.add(Box::new(Quad::new(
+        Point3::new(min.x(), min.y(), min.z()),
+        dz,
+        dy,
+        mat.clone(),
+    ))); // left
+    sides.add(Box::new(Quad::new(
+        Point3::new(min.x(), max.y(), max.z()),
+        dx,
+        -dz,
+        mat.clone(),
+    ))); // top
+    sides.add(Box::new(Quad::new(
+        Point3::new(min.x(), min.y(), min.z()),
+        dx,
+        dz,
+        mat,
+    ))); // bottom
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn hit_reports_distance_normal_and_uv() {
+        // Slanted parallelogram in the plane z = 1
+        let quad = Quad::new(
+            Point3::new(0.0, 0.0, 1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            material(),
+        );
+        let down = Vec3::new(0.0, 0.0, -1.0);
+
+        let r = Ray::new(Point3::new(1.5, 0.5, 4.0), down);
+        let rec = quad.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-12);
+        assert!(rec.front_face);
+        assert!((rec.u - 0.5).abs() < 1e-12 && (rec.v - 0.5).abs() < 1e-12);
+
+        // From behind, the normal faces the other way
+        let r = Ray::new(Point3::new(1.5, 0.5, -1.0), -down);
+        let rec = quad.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+        assert!(!rec.front_face);
+
+        // Inside the bounding box but outside the slanted edge
+        let r = Ray::new(Point3::new(0.2, 0.8, 4.0), down);
+        assert!(quad.hit(&r, Interval::new(0.0, f64::INFINITY)).is_none());
+        let r = Ray::new(Point3::new(1.5, 0.5, 4.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(quad.hit(&r, Interval::new(0.0, f64::INFINITY)).is_none());
+    }
+
+    #[test]
+    fn box_sides_face_outward() {
+        let cube = make_box(
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(-1.0, -1.0, -1.0),
+            material(),
+        );
+
+        for axis in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ] {
+            let r = Ray::new(Point3::new(0.2, 0.3, 0.1) + 5.0 * axis, -axis);
+            let rec = cube.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+            assert!(rec.front_face);
+            assert!((rec.normal - axis).length() < 1e-12);
+            assert!((rec.p.dot(&axis) - 1.0).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,532 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    math::solve_quadratic,
+    onb::Onb,
+    ray::Ray,
+    utils::{degrees_to_radians, PI},
+    vec3::{Point3, Vec3},
+};
+
+// The disk, cylinder and cone below are built in a local frame whose w axis is the shape's
+// axis. Partial shapes sweep an angle phi around w starting from the frame's u axis, and u
+// texture coordinates run from 0 to 1 over the swept angle.
+
+// Infinite plane through a point. UVs are the hit point's coordinates along the plane's
+// tangent axes, in world units, so textures tile across it.
+pub struct Plane {
+    point: Point3,
+    onb: Onb,
+    mat: Material,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Material) -> Self {
+        assert!(
+            normal.length_squared() > 0.0,
+            "plane normal must not be zero"
+        );
+
+        Plane {
+            point,
+            onb: Onb::new(&normal),
+            mat,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.onb.to_local(&(r.origin() - self.point));
+        let d = self.onb.to_local(&r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if d.z().abs() < 1e-8 {
+            return None;
+        }
+
+        let t = -o.z() / d.z();
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(r.at(t), t, r, &self.onb.w(), &self.mat);
+        rec.u = o.x() + t * d.x();
+        rec.v = o.y() + t * d.y();
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::UNIVERSE
+    }
+}
+
+// Flat disk facing along normal. v runs from 0 at the center to 1 at the rim.
+pub struct Disk {
+    center: Point3,
+    onb: Onb,
+    radius: f64,
+    phi_max: f64,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Material) -> Self {
+        assert!(
+            normal.length_squared() > 0.0,
+            "disk normal must not be zero"
+        );
+        assert!(radius > 0.0, "disk radius must be positive");
+
+        Disk {
+            center,
+            onb: Onb::new(&normal),
+            radius,
+            phi_max: 2.0 * PI,
+            mat,
+            bbox: disk_bounds(center, &normal, radius),
+        }
+    }
+
+    // Keep only the sector from 0 to the given angle
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.onb.to_local(&(r.origin() - self.center));
+        let d = self.onb.to_local(&r.direction());
+
+        let (t, phi, dist) = hit_cap(&o, &d, 0.0, self.radius, self.phi_max, &ray_t)?;
+
+        let mut rec = HitRecord::new(r.at(t), t, r, &self.onb.w(), &self.mat);
+        rec.u = phi / self.phi_max;
+        rec.v = dist / self.radius;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// Cylinder around the axis from base to top, open or closed with disk caps at both ends. v runs
+// from 0 at the base to 1 at the top on the side, and from 0 at the center to 1 at the rim on
+// the caps.
+pub struct Cylinder {
+    base: Point3,
+    onb: Onb,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    phi_max: f64,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3, top: Point3, radius: f64, capped: bool, mat: Material) -> Self {
+        let axis = top - base;
+        assert!(radius > 0.0, "cylinder radius must be positive");
+        assert!(
+            axis.length_squared() > 0.0,
+            "cylinder height must be positive"
+        );
+
+        Cylinder {
+            base,
+            onb: Onb::new(&axis),
+            radius,
+            height: axis.length(),
+            capped,
+            phi_max: 2.0 * PI,
+            mat,
+            bbox: Aabb::surrounding(
+                &disk_bounds(base, &axis, radius),
+                &disk_bounds(top, &axis, radius),
+            ),
+        }
+    }
+
+    // Keep only the part of the cylinder swept from 0 to the given angle
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.onb.to_local(&(r.origin() - self.base));
+        let d = self.onb.to_local(&r.direction());
+
+        let mut closest = ray_t;
+        let mut hit_record = None;
+
+        // Side: x^2 + y^2 = radius^2 for 0 <= z <= height
+        let a = d.x() * d.x() + d.y() * d.y();
+        let half_b = o.x() * d.x() + o.y() * d.y();
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+
+        for t in solve_quadratic(a, 2.0 * half_b, c) {
+            if !closest.surrounds(t) {
+                continue;
+            }
+
+            let p = o + t * d;
+            let phi = local_phi(p.x(), p.y());
+            if p.z() < 0.0 || p.z() > self.height || phi > self.phi_max {
+                continue;
+            }
+
+            let outward_normal =
+                self.onb
+                    .transform(&Vec3::new(p.x() / self.radius, p.y() / self.radius, 0.0));
+            let mut rec = HitRecord::new(r.at(t), t, r, &outward_normal, &self.mat);
+            rec.u = phi / self.phi_max;
+            rec.v = p.z() / self.height;
+
+            closest.max = t;
+            hit_record = Some(rec);
+            break;
+        }
+
+        if self.capped {
+            for (z, outward_normal) in [(0.0, -self.onb.w()), (self.height, self.onb.w())] {
+                if let Some((t, phi, dist)) =
+                    hit_cap(&o, &d, z, self.radius, self.phi_max, &closest)
+                {
+                    let mut rec = HitRecord::new(r.at(t), t, r, &outward_normal, &self.mat);
+                    rec.u = phi / self.phi_max;
+                    rec.v = dist / self.radius;
+
+                    closest.max = t;
+                    hit_record = Some(rec);
+                }
+            }
+        }
+
+        hit_record
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// Cone with its base disk at base and its tip at apex, open or closed with a base cap. v runs
+// from 0 at the base to 1 at the apex on the side, and from 0 at the center to 1 at the rim on
+// the cap.
+pub struct Cone {
+    base: Point3,
+    onb: Onb,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    phi_max: f64,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Cone {
+    pub fn new(base: Point3, apex: Point3, radius: f64, capped: bool, mat: Material) -> Self {
+        let axis = apex - base;
+        assert!(radius > 0.0, "cone radius must be positive");
+        assert!(axis.length_squared() > 0.0, "cone height must be positive");
+
+        Cone {
+            base,
+            onb: Onb::new(&axis),
+            radius,
+            height: axis.length(),
+            capped,
+            phi_max: 2.0 * PI,
+            mat,
+            bbox: Aabb::surrounding(
+                &disk_bounds(base, &axis, radius),
+                &Aabb::from_points(apex, apex),
+            ),
+        }
+    }
+
+    // Keep only the part of the cone swept from 0 to the given angle
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.phi_max = sweep_radians(degrees);
+        self
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.onb.to_local(&(r.origin() - self.base));
+        let d = self.onb.to_local(&r.direction());
+
+        let mut closest = ray_t;
+        let mut hit_record = None;
+
+        // Side: x^2 + y^2 = k^2 (height - z)^2 for 0 <= z <= height, where k is the slope of
+        // the radius. The equation also describes a mirrored cone above the apex, which the
+        // range check on z discards.
+        let k2 = (self.radius / self.height).powi(2);
+        let s = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let half_b = o.x() * d.x() + o.y() * d.y() + k2 * s * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * s * s;
+
+        for t in solve_quadratic(a, 2.0 * half_b, c) {
+            if !closest.surrounds(t) {
+                continue;
+            }
+
+            let p = o + t * d;
+            let phi = local_phi(p.x(), p.y());
+            if p.z() < 0.0 || p.z() > self.height || phi > self.phi_max {
+                continue;
+            }
+
+            // Gradient of the implicit surface
+            let gradient = Vec3::new(p.x(), p.y(), k2 * (self.height - p.z()));
+            let outward_normal = self.onb.transform(&gradient).unit_vector();
+            let mut rec = HitRecord::new(r.at(t), t, r, &outward_normal, &self.mat);
+            rec.u = phi / self.phi_max;
+            rec.v = p.z() / self.height;
+
+            closest.max = t;
+            hit_record = Some(rec);
+            break;
+        }
+
+        if self.capped {
+            if let Some((t, phi, dist)) = hit_cap(&o, &d, 0.0, self.radius, self.phi_max, &closest)
+            {
+                let mut rec = HitRecord::new(r.at(t), t, r, &-self.onb.w(), &self.mat);
+                rec.u = phi / self.phi_max;
+                rec.v = dist / self.radius;
+                hit_record = Some(rec);
+            }
+        }
+
+        hit_record
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// Intersects a ray given in local coordinates with the disk of the given radius in the plane
+// z = height, returning the hit's ray parameter, angle phi and distance from the axis
+fn hit_cap(
+    o: &Vec3,
+    d: &Vec3,
+    height: f64,
+    radius: f64,
+    phi_max: f64,
+    ray_t: &Interval,
+) -> Option<(f64, f64, f64)> {
+    if d.z().abs() < 1e-8 {
+        return None;
+    }
+
+    let t = (height - o.z()) / d.z();
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    let x = o.x() + t * d.x();
+    let y = o.y() + t * d.y();
+    let dist = (x * x + y * y).sqrt();
+    let phi = local_phi(x, y);
+    if dist > radius || phi > phi_max {
+        return None;
+    }
+
+    Some((t, phi, dist))
+}
+
+// Angle of the local point (x, y) around the w axis, in [0, 2pi)
+fn local_phi(x: f64, y: f64) -> f64 {
+    let phi = y.atan2(x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+fn sweep_radians(degrees: f64) -> f64 {
+    degrees_to_radians(degrees.clamp(0.0, 360.0))
+}
+
+// Bounds of a full disk, which extends radius * sqrt(1 - n_i^2) along each axis i
+fn disk_bounds(center: Point3, normal: &Vec3, radius: f64) -> Aabb {
+    let n = normal.unit_vector();
+    let extent = |n_i: f64| radius * (1.0 - n_i * n_i).max(0.0).sqrt();
+    let e = Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()));
+
+    Aabb::from_points(center - e, center + e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    fn hit<'a>(object: &'a dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord<'a>> {
+        object.hit(
+            &Ray::new(origin, direction),
+            Interval::new(0.0, f64::INFINITY),
+        )
+    }
+
+    fn assert_hit(rec: &HitRecord, t: f64, normal: Vec3, front_face: bool) {
+        assert!((rec.t - t).abs() < 1e-9, "t = {}, expected {}", rec.t, t);
+        assert!(
+            (rec.normal - normal).length() < 1e-9,
+            "normal {:?}, expected {:?}",
+            rec.normal,
+            normal
+        );
+        assert_eq!(rec.front_face, front_face);
+    }
+
+    #[test]
+    fn plane_hits_from_both_sides() {
+        let plane = Plane::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 3.0, 0.0),
+            material(),
+        );
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        let rec = hit(
+            &plane,
+            Point3::new(2.0, 3.0, 4.0),
+            Vec3::new(0.0, -2.0, 0.0),
+        )
+        .unwrap();
+        assert_hit(&rec, 1.0, up, true);
+        assert!((rec.p - Point3::new(2.0, 1.0, 4.0)).length() < 1e-12);
+
+        let rec = hit(
+            &plane,
+            Point3::new(0.0, -1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        )
+        .unwrap();
+        assert_hit(&rec, 2.0, -up, false);
+
+        assert!(hit(&plane, Point3::new(0.0, 3.0, 0.0), Vec3::new(1.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn disk_hits_inside_its_radius_and_sweep() {
+        let disk = Disk::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            material(),
+        );
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        let rec = hit(&disk, Point3::new(1.0, 3.0, 0.0), down).unwrap();
+        assert_hit(&rec, 3.0, Vec3::new(0.0, 1.0, 0.0), true);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        assert!(hit(&disk, Point3::new(2.5, 3.0, 0.0), down).is_none());
+
+        // Half a disk keeps exactly one of two opposite points
+        let half = Disk::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            material(),
+        )
+        .with_sweep(180.0);
+        let p = Point3::new(0.6, 3.0, 0.8);
+        let q = Point3::new(-0.6, 3.0, -0.8);
+        assert!(hit(&half, p, down).is_some() != hit(&half, q, down).is_some());
+    }
+
+    #[test]
+    fn cylinder_hits_side_and_caps() {
+        let base = Point3::new(0.0, 0.0, 0.0);
+        let top = Point3::new(0.0, 0.0, 2.0);
+        let capped = Cylinder::new(base, top, 1.0, true, material());
+        let open = Cylinder::new(base, top, 1.0, false, material());
+
+        let rec = hit(
+            &capped,
+            Point3::new(3.0, 0.0, 1.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert_hit(&rec, 2.0, Vec3::new(1.0, 0.0, 0.0), true);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+
+        // Diagonal ray that enters through the side and leaves through the inside
+        let dir = Vec3::new(-1.0, 1.0, 0.0).unit_vector();
+        let rec = hit(&open, Point3::new(0.0, 0.0, 1.5), dir).unwrap();
+        assert_hit(&rec, 1.0, -dir, false);
+
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        let rec = hit(&capped, Point3::new(0.5, 0.0, 5.0), down).unwrap();
+        assert_hit(&rec, 3.0, Vec3::new(0.0, 0.0, 1.0), true);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        let rec = hit(&capped, Point3::new(0.5, 0.0, -3.0), -down).unwrap();
+        assert_hit(&rec, 3.0, Vec3::new(0.0, 0.0, -1.0), true);
+
+        // Without caps, a ray along the axis passes straight through
+        assert!(hit(&open, Point3::new(0.5, 0.0, 5.0), down).is_none());
+    }
+
+    #[test]
+    fn cone_hits_side_and_cap() {
+        let base = Point3::new(0.0, 0.0, 0.0);
+        let apex = Point3::new(0.0, 0.0, 2.0);
+        let cone = Cone::new(base, apex, 1.0, true, material());
+
+        // Halfway up, the radius is 0.5 and the normal leans up by the slope
+        let rec = hit(&cone, Point3::new(2.0, 0.0, 1.0), Vec3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert_hit(&rec, 1.5, Vec3::new(2.0, 0.0, 1.0).unit_vector(), true);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+
+        // Above the apex the mirrored cone must not be hit
+        assert!(hit(&cone, Point3::new(2.0, 0.0, 3.0), Vec3::new(-1.0, 0.0, 0.0)).is_none());
+
+        let rec = hit(&cone, Point3::new(0.2, 0.1, -1.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert_hit(&rec, 1.0, Vec3::new(0.0, 0.0, -1.0), true);
+
+        let open = Cone::new(base, apex, 1.0, false, material());
+        let rec = hit(&open, Point3::new(0.2, 0.1, -1.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    #[should_panic(expected = "cylinder height must be positive")]
+    fn rejects_zero_height_cylinder() {
+        let p = Point3::new(1.0, 2.0, 3.0);
+        Cylinder::new(p, p, 1.0, true, material());
+    }
+
+    #[test]
+    #[should_panic(expected = "cone radius must be positive")]
+    fn rejects_zero_radius_cone() {
+        Cone::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            0.0,
+            false,
+            material(),
+        );
+    }
+}
@@ -1,14 +1,23 @@
 use crate::vec3::{Point3, Vec3};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
+    tm: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Ray { orig: origin, dir: direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Self {
+        Ray {
+            orig: origin,
+            dir: direction,
+            tm: time,
+        }
     }
 
     pub fn origin(&self) -> Point3 {
@@ -19,13 +28,11 @@ impl Ray {
         self.dir
     }
 
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         self.orig + t * self.dir
     }
 }
-
-impl Default for Ray {
-    fn default() -> Self {
-        Ray { orig: Point3::default(), dir: Vec3::default() }
-    }
-}
\ No newline at end of file
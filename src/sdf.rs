@@ -0,0 +1,552 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+// Shape described by a signed distance field: the distance from a point to the nearest surface,
+// negative inside. Sphere tracing needs the distance to never overestimate the true distance;
+// fractal estimators and distorting combinators only provide such a lower bound.
+pub trait Sdf {
+    fn distance(&self, p: &Point3) -> f64;
+
+    // Box enclosing the surface, used to limit sphere tracing
+    fn bounding_box(&self) -> Aabb;
+}
+
+// Renders a signed distance field by sphere tracing: stepping along the ray by the distance to
+// the nearest surface until the distance falls below epsilon
+pub struct SdfObject {
+    sdf: Box<dyn Sdf>,
+    mat: Material,
+    max_steps: usize,
+    epsilon: f64,
+    bbox: Aabb,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Box<dyn Sdf>, mat: Material) -> Self {
+        let bbox = sdf.bounding_box();
+        SdfObject {
+            sdf,
+            mat,
+            max_steps: 256,
+            epsilon: 1e-4,
+            bbox,
+        }
+    }
+
+    // Maximum number of steps along a ray before giving up on finding a surface
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    // Distance from the surface at which a ray counts as hitting it, which also sets the step
+    // used for numerical normals
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    // Unit normal from the central difference gradient of the distance field
+    fn normal(&self, p: &Point3) -> Vec3 {
+        let h = self.epsilon;
+        let dx = Vec3::new(h, 0.0, 0.0);
+        let dy = Vec3::new(0.0, h, 0.0);
+        let dz = Vec3::new(0.0, 0.0, h);
+
+        Vec3::new(
+            self.sdf.distance(&(*p + dx)) - self.sdf.distance(&(*p - dx)),
+            self.sdf.distance(&(*p + dy)) - self.sdf.distance(&(*p - dy)),
+            self.sdf.distance(&(*p + dz)) - self.sdf.distance(&(*p - dz)),
+        )
+        .unit_vector()
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let clipped = self.bbox.clip(r, ray_t)?;
+        let length = r.direction().length();
+        let mut t = clipped.min;
+        let mut steps = 0;
+
+        // A ray leaving the surface, e.g. after scattering off it, starts within epsilon of it.
+        // Step out of that band first so the ray doesn't hit the surface it came from. Rays
+        // entering the bounding box from outside can't have come from the surface.
+        let mut distance = self.sdf.distance(&r.at(t));
+        let leaving_surface = clipped.min == ray_t.min;
+        while leaving_surface && distance.abs() < self.epsilon && steps < self.max_steps {
+            t += 2.0 * self.epsilon / length;
+            distance = self.sdf.distance(&r.at(t));
+            steps += 1;
+        }
+
+        // March toward the surface from whichever side the ray is on, so rays travelling inside
+        // a refractive object find where they exit
+        let side = distance.signum();
+        while steps < self.max_steps && t < clipped.max {
+            let p = r.at(t);
+            let d = side * self.sdf.distance(&p);
+
+            if d < self.epsilon {
+                let outward_normal = self.normal(&p);
+                return Some(HitRecord::new(p, t, r, &outward_normal, &self.mat));
+            }
+
+            t += d / length;
+            steps += 1;
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+pub struct SdfSphere {
+    center: Point3,
+    radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(center: Point3, radius: f64) -> Self {
+        SdfSphere {
+            center,
+            radius: radius.max(0.0),
+        }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Point3) -> f64 {
+        (*p - self.center).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - rvec, self.center + rvec)
+    }
+}
+
+// Box with the given half extents whose edges and corners are rounded off with the given radius
+pub struct SdfRoundedBox {
+    center: Point3,
+    half_extents: Vec3,
+    radius: f64,
+}
+
+impl SdfRoundedBox {
+    pub fn new(center: Point3, half_extents: Vec3, radius: f64) -> Self {
+        let max_radius = half_extents
+            .x()
+            .min(half_extents.y())
+            .min(half_extents.z())
+            .max(0.0);
+
+        SdfRoundedBox {
+            center,
+            half_extents,
+            radius: radius.clamp(0.0, max_radius),
+        }
+    }
+}
+
+impl Sdf for SdfRoundedBox {
+    fn distance(&self, p: &Point3) -> f64 {
+        // Distance to the shrunk inner box, offset by the rounding radius
+        let local = *p - self.center;
+        let q = Vec3::new(
+            local.x().abs() - self.half_extents.x() + self.radius,
+            local.y().abs() - self.half_extents.y() + self.radius,
+            local.z().abs() - self.half_extents.z() + self.radius,
+        );
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+        let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+
+        outside + inside - self.radius
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(
+            self.center - self.half_extents,
+            self.center + self.half_extents,
+        )
+    }
+}
+
+// Power 8 Mandelbulb fractal, scaled up by the given factor from its natural size of about one
+// unit in radius
+pub struct Mandelbulb {
+    center: Point3,
+    scale: f64,
+    power: f64,
+    iterations: usize,
+}
+
+impl Mandelbulb {
+    pub fn new(center: Point3, scale: f64, iterations: usize) -> Self {
+        Mandelbulb {
+            center,
+            scale,
+            power: 8.0,
+            iterations,
+        }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: &Point3) -> f64 {
+        // Distance estimate from the running derivative of the iterated function
+        let c = (*p - self.center) / self.scale;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.length();
+
+        for _ in 0..self.iterations {
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+
+            // Raise z to the power in spherical coordinates, then add c
+            let theta = (z.z() / r).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+
+            let zr = r.powf(self.power);
+            z =
+                zr * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ) + c;
+            r = z.length();
+        }
+
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // The power 8 bulb lies within a radius of about 1.15
+        let extent = 1.2 * self.scale;
+        let e = Vec3::new(extent, extent, extent);
+        Aabb::from_points(self.center - e, self.center + e)
+    }
+}
+
+// Menger sponge fractal filling the cube of the given half size
+pub struct MengerSponge {
+    center: Point3,
+    half_size: f64,
+    iterations: usize,
+}
+
+impl MengerSponge {
+    pub fn new(center: Point3, half_size: f64, iterations: usize) -> Self {
+        MengerSponge {
+            center,
+            half_size,
+            iterations,
+        }
+    }
+}
+
+impl Sdf for MengerSponge {
+    fn distance(&self, p: &Point3) -> f64 {
+        let local = (*p - self.center) / self.half_size;
+
+        // Start from the unit cube and carve out the cross shaped holes of each level
+        let q = Vec3::new(
+            local.x().abs() - 1.0,
+            local.y().abs() - 1.0,
+            local.z().abs() - 1.0,
+        );
+        let mut d = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length()
+            + q.x().max(q.y()).max(q.z()).min(0.0);
+
+        let mut s = 1.0;
+        for _ in 0..self.iterations {
+            let a = Vec3::new(
+                (local.x() * s).rem_euclid(2.0) - 1.0,
+                (local.y() * s).rem_euclid(2.0) - 1.0,
+                (local.z() * s).rem_euclid(2.0) - 1.0,
+            );
+            s *= 3.0;
+
+            let r = Vec3::new(
+                (1.0 - 3.0 * a.x().abs()).abs(),
+                (1.0 - 3.0 * a.y().abs()).abs(),
+                (1.0 - 3.0 * a.z().abs()).abs(),
+            );
+            let da = r.x().max(r.y());
+            let db = r.y().max(r.z());
+            let dc = r.z().max(r.x());
+            let c = (da.min(db).min(dc) - 1.0) / s;
+
+            d = d.max(c);
+        }
+
+        d * self.half_size
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let e = Vec3::new(self.half_size, self.half_size, self.half_size);
+        Aabb::from_points(self.center - e, self.center + e)
+    }
+}
+
+pub struct SdfUnion {
+    a: Box<dyn Sdf>,
+    b: Box<dyn Sdf>,
+}
+
+impl SdfUnion {
+    pub fn new(a: Box<dyn Sdf>, b: Box<dyn Sdf>) -> Self {
+        SdfUnion { a, b }
+    }
+}
+
+impl Sdf for SdfUnion {
+    fn distance(&self, p: &Point3) -> f64 {
+        self.a.distance(p).min(self.b.distance(p))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::surrounding(&self.a.bounding_box(), &self.b.bounding_box())
+    }
+}
+
+// Union that blends the two shapes together over a distance of about k where they meet
+pub struct SdfSmoothUnion {
+    a: Box<dyn Sdf>,
+    b: Box<dyn Sdf>,
+    k: f64,
+}
+
+impl SdfSmoothUnion {
+    pub fn new(a: Box<dyn Sdf>, b: Box<dyn Sdf>, k: f64) -> Self {
+        SdfSmoothUnion {
+            a,
+            b,
+            k: k.max(0.0),
+        }
+    }
+}
+
+impl Sdf for SdfSmoothUnion {
+    fn distance(&self, p: &Point3) -> f64 {
+        let da = self.a.distance(p);
+        let db = self.b.distance(p);
+        if self.k == 0.0 {
+            return da.min(db);
+        }
+
+        // Polynomial smooth minimum
+        let h = (0.5 + 0.5 * (db - da) / self.k).clamp(0.0, 1.0);
+        db + (da - db) * h - self.k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // The blend pulls the surface out by at most k/4
+        let bbox = Aabb::surrounding(&self.a.bounding_box(), &self.b.bounding_box());
+        let delta = self.k / 2.0;
+        Aabb::new(
+            bbox.x.expand(delta),
+            bbox.y.expand(delta),
+            bbox.z.expand(delta),
+        )
+    }
+}
+
+// Shape a with shape b carved out of it
+pub struct SdfSubtract {
+    a: Box<dyn Sdf>,
+    b: Box<dyn Sdf>,
+}
+
+impl SdfSubtract {
+    pub fn new(a: Box<dyn Sdf>, b: Box<dyn Sdf>) -> Self {
+        SdfSubtract { a, b }
+    }
+}
+
+impl Sdf for SdfSubtract {
+    fn distance(&self, p: &Point3) -> f64 {
+        self.a.distance(p).max(-self.b.distance(p))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.a.bounding_box()
+    }
+}
+
+// Repeats a shape on a grid with the given spacing, with the given number of extra copies on
+// each side of the original along each axis. A spacing of zero disables repetition along that
+// axis. The shape must fit within one grid cell for the distance to stay valid.
+pub struct SdfRepeat {
+    sdf: Box<dyn Sdf>,
+    spacing: Vec3,
+    copies: [u32; 3],
+    origin: Point3,
+}
+
+impl SdfRepeat {
+    pub fn new(sdf: Box<dyn Sdf>, spacing: Vec3, copies: [u32; 3]) -> Self {
+        let origin = sdf.bounding_box().centroid();
+        SdfRepeat {
+            sdf,
+            spacing,
+            copies,
+            origin,
+        }
+    }
+}
+
+impl Sdf for SdfRepeat {
+    fn distance(&self, p: &Point3) -> f64 {
+        // Move the point into the nearest cell's copy of the original
+        let mut q = *p;
+        for axis in 0..3 {
+            let spacing = self.spacing[axis];
+            if spacing > 0.0 {
+                let limit = self.copies[axis] as f64;
+                let cell = ((p[axis] - self.origin[axis]) / spacing)
+                    .round()
+                    .clamp(-limit, limit);
+                q[axis] -= spacing * cell;
+            }
+        }
+
+        self.sdf.distance(&q)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.sdf.bounding_box();
+        let extent = |axis: usize| {
+            let reach = self.spacing[axis].max(0.0) * self.copies[axis] as f64;
+            let interval = bbox.axis_interval(axis);
+            Interval::new(interval.min - reach, interval.max + reach)
+        };
+
+        Aabb::new(extent(0), extent(1), extent(2))
+    }
+}
+
+// Twists a shape about the vertical axis through the center of its bounding box, rotating by
+// rate radians per unit of height
+pub struct SdfTwist {
+    sdf: Box<dyn Sdf>,
+    rate: f64,
+    center: Point3,
+    radius: f64,
+}
+
+impl SdfTwist {
+    pub fn new(sdf: Box<dyn Sdf>, rate: f64) -> Self {
+        let bbox = sdf.bounding_box();
+        let center = bbox.centroid();
+
+        // Farthest horizontal distance from the axis, which bounds the shape at any rotation
+        let radius = (0.5 * bbox.x.size()).hypot(0.5 * bbox.z.size());
+
+        SdfTwist {
+            sdf,
+            rate,
+            center,
+            radius,
+        }
+    }
+}
+
+impl Sdf for SdfTwist {
+    fn distance(&self, p: &Point3) -> f64 {
+        // Rotate the point backwards about the axis by the twist angle at its height
+        let local = *p - self.center;
+        let angle = -self.rate * local.y();
+        let (sin, cos) = angle.sin_cos();
+        let q = Vec3::new(
+            cos * local.x() - sin * local.z(),
+            local.y(),
+            sin * local.x() + cos * local.z(),
+        );
+
+        // Twisting stretches distances by up to 1 + |rate| * radius, so scale the distance down
+        // to keep it a lower bound
+        self.sdf.distance(&(self.center + q)) / (1.0 + self.rate.abs() * self.radius)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.sdf.bounding_box();
+        let horizontal = |c: f64| Interval::new(c - self.radius, c + self.radius);
+
+        Aabb::new(
+            horizontal(self.center.x()),
+            bbox.y,
+            horizontal(self.center.z()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn object(sdf: Box<dyn Sdf>) -> SdfObject {
+        SdfObject::new(sdf, Material::lambertian(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn sphere_traces_to_its_surface() {
+        let sphere = object(Box::new(SdfSphere::new(Point3::new(1.0, 2.0, 3.0), 2.0)));
+
+        // Unnormalized direction, so t is in units of its length
+        let r = Ray::new(Point3::new(1.0, 2.0, 10.0), Vec3::new(0.0, 0.0, -2.0));
+        let rec = sphere.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-4);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-4);
+        assert!(rec.front_face);
+
+        // Starting inside, the ray finds where it leaves
+        let r = Ray::new(
+            Point3::new(1.0, 2.0, 3.0),
+            Vec3::new(1.0, 1.0, 0.0).unit_vector(),
+        );
+        let rec = sphere.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-4);
+        assert!(!rec.front_face);
+
+        let r = Ray::new(Point3::new(4.0, 2.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(sphere.hit(&r, Interval::new(0.0, f64::INFINITY)).is_none());
+    }
+
+    #[test]
+    fn rounded_box_has_flat_faces_and_round_corners() {
+        let rounded = object(Box::new(SdfRoundedBox::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 2.0, 1.0),
+            0.5,
+        )));
+
+        let r = Ray::new(Point3::new(0.2, 5.0, -0.3), Vec3::new(0.0, -1.0, 0.0));
+        let rec = rounded.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-4);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-4);
+
+        // Along the diagonal, the corner is cut back to the rounding sphere around (0.5, 1.5, 0.5)
+        let dir = Vec3::new(1.0, 1.0, 1.0).unit_vector();
+        let r = Ray::new(Point3::new(0.5, 1.5, 0.5) + 3.0 * dir, -dir);
+        let rec = rounded.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-4);
+        assert!((rec.normal - dir).length() < 1e-3);
+    }
+}
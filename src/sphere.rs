@@ -1,30 +1,73 @@
+use rand::Rng;
+
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     interval::Interval,
+    light::Light,
     material::Material,
+    onb::Onb,
     ray::Ray,
-    vec3::Point3,
+    utils::PI,
+    vec3::{Point3, Vec3},
 };
 
 pub struct Sphere {
-    center: Point3,
+    center: Ray, // center at time 0 and its motion per unit of time
     radius: f64,
     mat: Material,
+    bbox: Aabb,
 }
 
 impl Sphere {
+    // Stationary sphere
     pub fn new(center: Point3, radius: f64, mat: Material) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+
         Sphere {
-            center,
-            radius: radius.max(0.0),
+            center: Ray::new(center, Vec3::new(0.0, 0.0, 0.0)),
+            radius,
             mat,
+            bbox: Aabb::from_points(center - rvec, center + rvec),
         }
     }
+
+    // Moving sphere, linearly interpolated from center1 at time 0 to center2 at time 1
+    pub fn new_moving(center1: Point3, center2: Point3, radius: f64, mat: Material) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let box1 = Aabb::from_points(center1 - rvec, center1 + rvec);
+        let box2 = Aabb::from_points(center2 - rvec, center2 + rvec);
+
+        Sphere {
+            center: Ray::new(center1, center2 - center1),
+            radius,
+            mat,
+            bbox: Aabb::surrounding(&box1, &box2),
+        }
+    }
+
+    // Direction from origin to the center and 1 - cos(theta_max) of the cone the sphere
+    // subtends at the given time, or None if origin is inside the sphere. The difference is
+    // computed without cancellation so distant, small spheres still get an accurate cone.
+    fn cone(&self, origin: &Point3, time: f64) -> Option<(Vec3, f64)> {
+        let axis = self.center.at(time) - *origin;
+        let distance_squared = axis.length_squared();
+        let sin2_theta_max = self.radius * self.radius / distance_squared;
+        if sin2_theta_max >= 1.0 {
+            return None;
+        }
+
+        let cos_theta_max = (1.0 - sin2_theta_max).sqrt();
+        Some((axis, sin2_theta_max / (1.0 + cos_theta_max)))
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = self.center - r.origin();
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let current_center = self.center.at(r.time());
+        let oc = current_center - r.origin();
         let a = r.direction().length_squared();
         let h = r.direction().dot(&oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -46,14 +89,83 @@ impl Hittable for Sphere {
         }
 
         let hit_point = r.at(root);
-        let outward_normal = (hit_point - self.center) / self.radius;
+        let outward_normal = (hit_point - current_center) / self.radius;
+
+        let mut rec = HitRecord::new(hit_point, root, r, &outward_normal, &self.mat);
+        (rec.u, rec.v) = sphere_uv(&outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray) -> Vec<(HitRecord<'_>, HitRecord<'_>)> {
+        let current_center = self.center.at(r.time());
+        let oc = current_center - r.origin();
+        let a = r.direction().length_squared();
+        let h = r.direction().dot(&oc);
+        let c = oc.length_squared() - self.radius * self.radius;
 
-        Some(HitRecord::new(
-            hit_point,
-            root,
-            r,
-            &outward_normal,
-            self.mat,
-        ))
+        let discriminant = h * h - a * c;
+        if discriminant <= 0.0 {
+            return Vec::new();
+        }
+
+        let sqrtd = discriminant.sqrt();
+        let record = |root: f64| {
+            let hit_point = r.at(root);
+            let outward_normal = (hit_point - current_center) / self.radius;
+            let mut rec = HitRecord::new(hit_point, root, r, &outward_normal, &self.mat);
+            (rec.u, rec.v) = sphere_uv(&outward_normal);
+            rec
+        };
+
+        vec![(record((h - sqrtd) / a), record((h + sqrtd) / a))]
     }
+
+    fn collect_lights<'a>(&'a self, lights: &mut Vec<&'a dyn Light>) {
+        if self.mat.is_emissive() && self.radius > 0.0 {
+            lights.push(self);
+        }
+    }
+}
+
+impl Light for Sphere {
+    // Directions are sampled uniformly within the cone the sphere subtends from origin
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        let Some((axis, one_minus_cos_theta_max)) = self.cone(origin, time) else {
+            return 0.0;
+        };
+
+        let cos_theta = direction.dot(&axis) / (direction.length() * axis.length());
+        if cos_theta < 1.0 - one_minus_cos_theta_max {
+            return 0.0;
+        }
+
+        1.0 / (2.0 * PI * one_minus_cos_theta_max)
+    }
+
+    fn random(&self, origin: &Point3, time: f64) -> Vec3 {
+        let Some((axis, one_minus_cos_theta_max)) = self.cone(origin, time) else {
+            return Vec3::random_unit_vector();
+        };
+
+        let mut rng = rand::thread_rng();
+        let z = 1.0 - rng.gen::<f64>() * one_minus_cos_theta_max;
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+        Onb::new(&axis).transform(&local)
+    }
+}
+
+// Texture coordinates of a point on the unit sphere: u is the angle around the y axis from -x,
+// and v the angle from the bottom pole at y = -1 to the top, both scaled to [0, 1]
+fn sphere_uv(p: &Point3) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+
+    (phi / (2.0 * PI), theta / PI)
 }
@@ -0,0 +1,609 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    material::Material,
+    mesh::{MeshBuffers, TriangleMesh},
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubdivisionScheme {
+    // Loop subdivision, for triangle meshes. Other polygons are split into triangle fans
+    // first.
+    Loop,
+    // Catmull-Clark subdivision, for quad meshes. Other polygons are allowed and become quads
+    // after the first level.
+    CatmullClark,
+}
+
+// Polygon cage of a subdivision surface. Edges marked as creases, and boundary edges, stay
+// sharp: they are refined as cubic B-spline curves instead of being smoothed into the
+// neighboring faces. Vertices where three or more sharp edges meet, and boundary vertices with
+// no other edges, stay fixed as corners.
+#[derive(Debug, Clone, Default)]
+pub struct ControlCage {
+    pub positions: Vec<Point3>,
+    pub faces: Vec<Vec<usize>>,
+    pub creases: Vec<[usize; 2]>,
+}
+
+impl ControlCage {
+    pub fn new(positions: Vec<Point3>, faces: Vec<Vec<usize>>) -> Self {
+        assert!(
+            faces.iter().all(|face| face.len() >= 3),
+            "cage face has fewer than 3 vertices"
+        );
+        assert!(
+            faces.iter().flatten().all(|&i| i < positions.len()),
+            "cage face index out of range"
+        );
+        // Each corner must be its own vertex, or faces lose their edges and opposite vertices
+        assert!(
+            faces
+                .iter()
+                .all(|face| face.iter().collect::<HashSet<_>>().len() == face.len()),
+            "cage face repeats a vertex"
+        );
+
+        ControlCage {
+            positions,
+            faces,
+            creases: Vec::new(),
+        }
+    }
+
+    pub fn from_triangles(positions: Vec<Point3>, triangles: &[[usize; 3]]) -> Self {
+        Self::new(positions, triangles.iter().map(|t| t.to_vec()).collect())
+    }
+
+    pub fn add_crease(&mut self, a: usize, b: usize) {
+        self.creases.push([a, b]);
+    }
+
+    // Refine the cage the given number of times with the scheme, move the vertices onto the
+    // limit surface and return the result as a triangle mesh. Normals are smooth except across
+    // creases and boundaries.
+    pub fn subdivide(
+        &self,
+        scheme: SubdivisionScheme,
+        levels: usize,
+        mat: Material,
+    ) -> TriangleMesh {
+        let mut cage = match scheme {
+            SubdivisionScheme::Loop => self.triangulated(),
+            SubdivisionScheme::CatmullClark => self.clone(),
+        };
+        for _ in 0..levels {
+            let topology = Topology::new(&cage);
+            cage = match scheme {
+                SubdivisionScheme::Loop => topology.loop_step(&cage),
+                SubdivisionScheme::CatmullClark => topology.catmull_clark_step(&cage),
+            };
+        }
+
+        // The Catmull-Clark limit rule only holds on quad meshes, which the cage is after any
+        // refinement
+        let topology = Topology::new(&cage);
+        if scheme == SubdivisionScheme::Loop || levels > 0 {
+            cage.positions = topology.limit_positions(&cage, scheme);
+        }
+
+        topology.to_mesh(&cage, mat)
+    }
+
+    // Copy of the cage with every polygon split into a fan of triangles around its first
+    // vertex. The new diagonals are smooth; creases stay on the original edges.
+    fn triangulated(&self) -> ControlCage {
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |i| vec![face[0], face[i], face[i + 1]]))
+            .collect();
+
+        ControlCage {
+            positions: self.positions.clone(),
+            faces,
+            creases: self.creases.clone(),
+        }
+    }
+}
+
+type EdgeKey = (usize, usize);
+
+fn edge_key(a: usize, b: usize) -> EdgeKey {
+    (a.min(b), a.max(b))
+}
+
+struct Edge {
+    faces: Vec<usize>,
+    sharp: bool,
+}
+
+// Connectivity of a cage: its edges in order of first appearance, and the edges and faces
+// around each vertex
+struct Topology {
+    edge_keys: Vec<EdgeKey>,
+    edges: HashMap<EdgeKey, Edge>,
+    vertex_edges: Vec<Vec<EdgeKey>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(cage: &ControlCage) -> Self {
+        let creases: HashSet<EdgeKey> = cage.creases.iter().map(|&[a, b]| edge_key(a, b)).collect();
+        let mut topology = Topology {
+            edge_keys: Vec::new(),
+            edges: HashMap::new(),
+            vertex_edges: vec![Vec::new(); cage.positions.len()],
+            vertex_faces: vec![Vec::new(); cage.positions.len()],
+        };
+
+        for (f, face) in cage.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let (a, b) = (face[i], face[(i + 1) % face.len()]);
+                let key = edge_key(a, b);
+                topology.vertex_faces[a].push(f);
+
+                let edge = topology.edges.entry(key).or_insert_with(|| {
+                    topology.edge_keys.push(key);
+                    topology.vertex_edges[a].push(key);
+                    topology.vertex_edges[b].push(key);
+                    Edge {
+                        faces: Vec::new(),
+                        sharp: creases.contains(&key),
+                    }
+                });
+                edge.faces.push(f);
+            }
+        }
+
+        // Boundary and non-manifold edges are always sharp
+        for edge in topology.edges.values_mut() {
+            edge.sharp |= edge.faces.len() != 2;
+        }
+
+        topology
+    }
+
+    fn edge_index(&self) -> HashMap<EdgeKey, usize> {
+        self.edge_keys
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| (key, i))
+            .collect()
+    }
+
+    // Other ends of the sharp edges at a vertex
+    fn sharp_neighbors(&self, v: usize) -> Vec<usize> {
+        self.vertex_edges[v]
+            .iter()
+            .filter(|key| self.edges[key].sharp)
+            .map(|&(a, b)| if a == v { b } else { a })
+            .collect()
+    }
+
+    fn neighbors(&self, v: usize) -> impl Iterator<Item = usize> + '_ {
+        self.vertex_edges[v]
+            .iter()
+            .map(move |&(a, b)| if a == v { b } else { a })
+    }
+
+    // Position of a vertex that is not smooth, or None for smooth vertices. Vertices on one
+    // sharp curve follow the curve with the given weights for (neighbors, vertex); corners,
+    // including boundary vertices of a single face, and isolated vertices stay fixed.
+    fn sharp_vertex(&self, cage: &ControlCage, v: usize, weights: (f64, f64)) -> Option<Point3> {
+        let p = cage.positions[v];
+        let sharp = self.sharp_neighbors(v);
+        match sharp.len() {
+            // A vertex with a single sharp edge, a dart, is smooth
+            0 | 1 if !self.vertex_edges[v].is_empty() => None,
+            2 if self.vertex_edges[v].len() > 2 => Some(
+                weights.0 * (cage.positions[sharp[0]] + cage.positions[sharp[1]]) + weights.1 * p,
+            ),
+            _ => Some(p),
+        }
+    }
+
+    fn loop_step(&self, cage: &ControlCage) -> ControlCage {
+        let vertex_count = cage.positions.len();
+        let edge_index = self.edge_index();
+        let mut positions = Vec::with_capacity(vertex_count + self.edge_keys.len());
+
+        // Even vertices
+        for v in 0..vertex_count {
+            let p = self
+                .sharp_vertex(cage, v, (1.0 / 8.0, 3.0 / 4.0))
+                .unwrap_or_else(|| {
+                    let n = self.vertex_edges[v].len();
+                    let beta = if n == 3 {
+                        3.0 / 16.0
+                    } else {
+                        3.0 / (8.0 * n as f64)
+                    };
+                    let sum = self
+                        .neighbors(v)
+                        .fold(Vec3::new(0.0, 0.0, 0.0), |sum, w| sum + cage.positions[w]);
+                    (1.0 - n as f64 * beta) * cage.positions[v] + beta * sum
+                });
+            positions.push(p);
+        }
+
+        // Odd vertices, one per edge
+        for key in &self.edge_keys {
+            let edge = &self.edges[key];
+            let (a, b) = (cage.positions[key.0], cage.positions[key.1]);
+            let p = if edge.sharp {
+                0.5 * (a + b)
+            } else {
+                let opposite = edge.faces.iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, &f| {
+                    let c = cage.faces[f]
+                        .iter()
+                        .find(|&&i| i != key.0 && i != key.1)
+                        .unwrap();
+                    sum + cage.positions[*c]
+                });
+                3.0 / 8.0 * (a + b) + 1.0 / 8.0 * opposite
+            };
+            positions.push(p);
+        }
+
+        let odd = |a: usize, b: usize| vertex_count + edge_index[&edge_key(a, b)];
+        let mut faces = Vec::with_capacity(4 * cage.faces.len());
+        for face in &cage.faces {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let (ab, bc, ca) = (odd(a, b), odd(b, c), odd(c, a));
+            faces.push(vec![a, ab, ca]);
+            faces.push(vec![ab, b, bc]);
+            faces.push(vec![ca, bc, c]);
+            faces.push(vec![ab, bc, ca]);
+        }
+
+        ControlCage {
+            positions,
+            faces,
+            creases: self.split_creases(vertex_count, &edge_index),
+        }
+    }
+
+    fn catmull_clark_step(&self, cage: &ControlCage) -> ControlCage {
+        let vertex_count = cage.positions.len();
+        let edge_index = self.edge_index();
+        let face_offset = vertex_count + self.edge_keys.len();
+
+        let face_points: Vec<Point3> = cage
+            .faces
+            .iter()
+            .map(|face| {
+                face.iter()
+                    .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &i| sum + cage.positions[i])
+                    / face.len() as f64
+            })
+            .collect();
+
+        let mut positions = Vec::with_capacity(face_offset + face_points.len());
+
+        // Vertex points
+        for v in 0..vertex_count {
+            let p = self
+                .sharp_vertex(cage, v, (1.0 / 8.0, 3.0 / 4.0))
+                .unwrap_or_else(|| {
+                    let n = self.vertex_edges[v].len() as f64;
+                    let faces = &self.vertex_faces[v];
+                    let f = faces
+                        .iter()
+                        .fold(Vec3::new(0.0, 0.0, 0.0), |sum, &i| sum + face_points[i])
+                        / faces.len() as f64;
+                    let r = self.neighbors(v).fold(Vec3::new(0.0, 0.0, 0.0), |sum, w| {
+                        sum + 0.5 * (cage.positions[v] + cage.positions[w])
+                    }) / n;
+                    (f + 2.0 * r + (n - 3.0) * cage.positions[v]) / n
+                });
+            positions.push(p);
+        }
+
+        // Edge points
+        for key in &self.edge_keys {
+            let edge = &self.edges[key];
+            let (a, b) = (cage.positions[key.0], cage.positions[key.1]);
+            let p = if edge.sharp {
+                0.5 * (a + b)
+            } else {
+                0.25 * (a + b + face_points[edge.faces[0]] + face_points[edge.faces[1]])
+            };
+            positions.push(p);
+        }
+
+        positions.extend(face_points);
+
+        // Each face splits into one quad per corner
+        let edge_point = |a: usize, b: usize| vertex_count + edge_index[&edge_key(a, b)];
+        let mut faces = Vec::new();
+        for (f, face) in cage.faces.iter().enumerate() {
+            let n = face.len();
+            for i in 0..n {
+                let (prev, v, next) = (face[(i + n - 1) % n], face[i], face[(i + 1) % n]);
+                faces.push(vec![
+                    v,
+                    edge_point(v, next),
+                    face_offset + f,
+                    edge_point(prev, v),
+                ]);
+            }
+        }
+
+        ControlCage {
+            positions,
+            faces,
+            creases: self.split_creases(vertex_count, &edge_index),
+        }
+    }
+
+    // Each crease edge becomes two crease edges through its new midpoint vertex
+    fn split_creases(
+        &self,
+        vertex_count: usize,
+        edge_index: &HashMap<EdgeKey, usize>,
+    ) -> Vec<[usize; 2]> {
+        self.edge_keys
+            .iter()
+            .filter(|key| {
+                let edge = &self.edges[key];
+                edge.sharp && edge.faces.len() == 2
+            })
+            .flat_map(|&(a, b)| {
+                let mid = vertex_count + edge_index[&(a, b)];
+                [[a, mid], [mid, b]]
+            })
+            .collect()
+    }
+
+    // Positions of the vertices on the limit surface
+    fn limit_positions(&self, cage: &ControlCage, scheme: SubdivisionScheme) -> Vec<Point3> {
+        (0..cage.positions.len())
+            .map(|v| {
+                self.sharp_vertex(cage, v, (1.0 / 6.0, 2.0 / 3.0))
+                    .unwrap_or_else(|| {
+                        let p = cage.positions[v];
+                        let n = self.vertex_edges[v].len() as f64;
+                        let neighbors = self
+                            .neighbors(v)
+                            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, w| sum + cage.positions[w]);
+
+                        match scheme {
+                            SubdivisionScheme::Loop => {
+                                let beta = if n == 3.0 {
+                                    3.0 / 16.0
+                                } else {
+                                    3.0 / (8.0 * n)
+                                };
+                                let chi = 1.0 / (n + 3.0 / (8.0 * beta));
+                                (1.0 - n * chi) * p + chi * neighbors
+                            }
+                            SubdivisionScheme::CatmullClark => {
+                                // Diagonally opposite corners of the surrounding quads
+                                let diagonals = self.vertex_faces[v].iter().fold(
+                                    Vec3::new(0.0, 0.0, 0.0),
+                                    |sum, &f| {
+                                        let face = &cage.faces[f];
+                                        let i = face.iter().position(|&i| i == v).unwrap();
+                                        sum + cage.positions[face[(i + 2) % face.len()]]
+                                    },
+                                );
+                                (n * n * p + 4.0 * neighbors + diagonals) / (n * (n + 5.0))
+                            }
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    // Triangulate the cage into a mesh with vertex normals. Corners of faces meeting at a
+    // vertex across smooth edges share one averaged normal; sharp edges split the vertex so
+    // creases stay crisp.
+    fn to_mesh(&self, cage: &ControlCage, mat: Material) -> TriangleMesh {
+        // Union-find over face corners, indexed by the offset of the corner in the face list
+        let mut corner_offsets = Vec::with_capacity(cage.faces.len());
+        let mut corner_count = 0;
+        for face in &cage.faces {
+            corner_offsets.push(corner_count);
+            corner_count += face.len();
+        }
+        let mut parent: Vec<usize> = (0..corner_count).collect();
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let corner = |f: usize, v: usize| {
+            let i = cage.faces[f].iter().position(|&i| i == v).unwrap();
+            corner_offsets[f] + i
+        };
+        for (key, edge) in &self.edges {
+            if edge.sharp {
+                continue;
+            }
+            let (f0, f1) = (edge.faces[0], edge.faces[1]);
+            for v in [key.0, key.1] {
+                let a = find(&mut parent, corner(f0, v));
+                let b = find(&mut parent, corner(f1, v));
+                parent[a] = b;
+            }
+        }
+
+        // One output vertex per group of joined corners, with the area weighted sum of the
+        // normals of the faces in the group
+        let mut group_vertex = HashMap::new();
+        let mut buffers = MeshBuffers::new(Vec::new());
+        let mut corner_vertex = vec![0; corner_count];
+        for (f, face) in cage.faces.iter().enumerate() {
+            let normal = face_normal(cage, face);
+            for (i, &v) in face.iter().enumerate() {
+                let root = find(&mut parent, corner_offsets[f] + i);
+                let index = *group_vertex.entry(root).or_insert_with(|| {
+                    buffers.positions.push(cage.positions[v]);
+                    buffers.normals.push(Vec3::new(0.0, 0.0, 0.0));
+                    buffers.positions.len() - 1
+                });
+                buffers.normals[index] += normal;
+                corner_vertex[corner_offsets[f] + i] = index;
+            }
+        }
+        for normal in buffers.normals.iter_mut() {
+            if normal.length_squared() > 0.0 {
+                *normal = normal.unit_vector();
+            }
+        }
+
+        let mut triangles = Vec::new();
+        for (f, face) in cage.faces.iter().enumerate() {
+            let vertex = |i: usize| corner_vertex[corner_offsets[f] + i];
+            for i in 1..face.len() - 1 {
+                triangles.push([vertex(0), vertex(i), vertex(i + 1)]);
+            }
+        }
+
+        TriangleMesh::new(Arc::new(buffers), triangles, mat)
+    }
+}
+
+// Normal of a polygon scaled by its area, summed over a triangle fan
+fn face_normal(cage: &ControlCage, face: &[usize]) -> Vec3 {
+    let p0 = cage.positions[face[0]];
+    (1..face.len() - 1).fold(Vec3::new(0.0, 0.0, 0.0), |sum, i| {
+        let p1 = cage.positions[face[i]];
+        let p2 = cage.positions[face[i + 1]];
+        sum + 0.5 * (p1 - p0).cross(&(p2 - p0))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    fn tetrahedron() -> ControlCage {
+        ControlCage::from_triangles(
+            vec![
+                Point3::new(1.0, 1.0, 1.0),
+                Point3::new(1.0, -1.0, -1.0),
+                Point3::new(-1.0, 1.0, -1.0),
+                Point3::new(-1.0, -1.0, 1.0),
+            ],
+            &[[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]],
+        )
+    }
+
+    // Quad cube from -1 to 1 with outward facing quads
+    fn cube() -> ControlCage {
+        let positions = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+                Point3::new(coordinate(1), coordinate(2), coordinate(4))
+            })
+            .collect();
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+        ControlCage::new(positions, faces)
+    }
+
+    // Vertex, edge and face counts of a cage
+    fn counts(cage: &ControlCage) -> (usize, usize, usize) {
+        let topology = Topology::new(cage);
+        (
+            cage.positions.len(),
+            topology.edge_keys.len(),
+            cage.faces.len(),
+        )
+    }
+
+    fn max_abs_coordinate(p: &Point3) -> f64 {
+        p.x().abs().max(p.y().abs()).max(p.z().abs())
+    }
+
+    #[test]
+    fn loop_step_splits_each_triangle_in_four() {
+        let cage = tetrahedron();
+        let (v, e, f) = counts(&cage);
+        let refined = Topology::new(&cage).loop_step(&cage);
+
+        assert_eq!(counts(&refined), (v + e, 2 * e + 3 * f, 4 * f));
+        assert!(refined.faces.iter().all(|face| face.len() == 3));
+    }
+
+    #[test]
+    fn catmull_clark_step_splits_each_face_into_quads() {
+        let cage = cube();
+        let (v, e, f) = counts(&cage);
+        let corners: usize = cage.faces.iter().map(Vec::len).sum();
+        let refined = Topology::new(&cage).catmull_clark_step(&cage);
+
+        assert_eq!(counts(&refined), (v + e + f, 2 * e + corners, corners));
+        assert!(refined.faces.iter().all(|face| face.len() == 4));
+    }
+
+    #[test]
+    fn creases_keep_the_cube_sharp() {
+        let mut creased = cube();
+        for face in creased.faces.clone() {
+            for i in 0..4 {
+                creased.add_crease(face[i], face[(i + 1) % 4]);
+            }
+        }
+
+        // With every edge creased, the faces stay flat and the corners stay put
+        let mesh = creased.subdivide(SubdivisionScheme::CatmullClark, 2, material());
+        let positions = &mesh.buffers().positions;
+        for p in positions {
+            assert!((max_abs_coordinate(p) - 1.0).abs() < 1e-12, "{:?}", p);
+        }
+        for corner in &creased.positions {
+            assert!(positions.iter().any(|p| (*p - *corner).length() < 1e-12));
+        }
+
+        // Without creases the cube is rounded off
+        let mesh = cube().subdivide(SubdivisionScheme::CatmullClark, 2, material());
+        assert!(mesh
+            .buffers()
+            .positions
+            .iter()
+            .all(|p| max_abs_coordinate(p) < 1.0));
+    }
+
+    #[test]
+    fn loop_triangulates_a_quad_cage() {
+        let mesh = cube().subdivide(SubdivisionScheme::Loop, 2, material());
+
+        assert_eq!(mesh.triangle_count(), 12 * 16);
+        let buffers = mesh.buffers();
+        for (p, n) in buffers.positions.iter().zip(&buffers.normals) {
+            assert!(p.length().is_finite());
+            // The cube is symmetric about the origin, so normals point away from it
+            assert!((n.length() - 1.0).abs() < 1e-9 && n.dot(p) > 0.0);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cage face repeats a vertex")]
+    fn rejects_faces_with_repeated_vertices() {
+        ControlCage::from_triangles(
+            vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0)],
+            &[[0, 0, 1]],
+        );
+    }
+}
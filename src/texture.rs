@@ -0,0 +1,572 @@
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    noise::{Perlin, Worley},
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            TextureError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Io { source, .. } => Some(source),
+            TextureError::Format { .. } => None,
+        }
+    }
+}
+
+// Spatially varying material parameter, looked up by the surface coordinates (u, v) and the
+// hit point p of a hit record
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    // Look up the texture for a hit, for textures that need more of the record than (u, v)
+    // and p, such as the vertex color
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        SolidColor { albedo }
+    }
+
+    pub fn from_rgb(red: f64, green: f64, blue: f64) -> Self {
+        SolidColor::new(Color::new(red, green, blue))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+// Checkerboard of unit cubes of the given size filling space, alternating between two textures.
+// Being solid rather than mapped by (u, v), it needs no surface coordinates and never stretches.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, c1: Color, c2: Color) -> Self {
+        CheckerTexture::new(
+            scale,
+            Arc::new(SolidColor::new(c1)),
+            Arc::new(SolidColor::new(c2)),
+        )
+    }
+
+    // Texture of the cell containing p
+    fn cell_texture(&self, p: &Point3) -> &dyn Texture {
+        let sum: i64 = (0..3)
+            .map(|axis| (self.inv_scale * p[axis]).floor() as i64)
+            .sum();
+
+        if sum % 2 == 0 {
+            self.even.as_ref()
+        } else {
+            self.odd.as_ref()
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.cell_texture(p).value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.cell_texture(&rec.p).value_at(rec)
+    }
+}
+
+// Vertex colors of a mesh, interpolated across each face and multiplied by a scale. Surfaces
+// without vertex colors show the scale alone.
+pub struct VertexColorTexture {
+    scale: Color,
+}
+
+impl VertexColorTexture {
+    pub fn new() -> Self {
+        VertexColorTexture {
+            scale: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: Color) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+impl Default for VertexColorTexture {
+    fn default() -> Self {
+        VertexColorTexture::new()
+    }
+}
+
+impl Texture for VertexColorTexture {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.scale
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.scale * rec.color
+    }
+}
+
+// Procedural pattern of a NoiseTexture, each scaled to [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    Perlin,
+    Fbm { octaves: u32 },
+    Turbulence { octaves: u32 },
+    // Veins of turbulence running through bands along z
+    Marble { octaves: u32 },
+    // Rings around the y axis, wobbled by fbm
+    Wood { octaves: u32 },
+    // Distance to the nearest Worley feature point, dark at the points
+    WorleyF1,
+    // Distance to the second nearest feature point
+    WorleyF2,
+    // Difference between the two, dark along the edges of the Voronoi cells
+    WorleyEdges,
+}
+
+// Seeded noise pattern evaluated at the hit point, blending between two colors. Grey patterns,
+// the default, can also drive scalar parameters such as Metal fuzz.
+pub struct NoiseTexture {
+    perlin: Perlin,
+    worley: Worley,
+    pattern: NoisePattern,
+    scale: f64,
+    colors: [Color; 2],
+}
+
+impl NoiseTexture {
+    // Noise with features about 1 / scale apart
+    pub fn new(seed: u64, pattern: NoisePattern, scale: f64) -> Self {
+        NoiseTexture {
+            perlin: Perlin::new(seed),
+            worley: Worley::new(seed),
+            pattern,
+            scale,
+            colors: [Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)],
+        }
+    }
+
+    // Colors at pattern values 0 and 1
+    pub fn with_colors(mut self, low: Color, high: Color) -> Self {
+        self.colors = [low, high];
+        self
+    }
+
+    fn pattern_value(&self, p: &Point3) -> f64 {
+        let q = self.scale * *p;
+        let value = match self.pattern {
+            NoisePattern::Perlin => 0.5 * (1.0 + self.perlin.noise(&q)),
+            NoisePattern::Fbm { octaves } => 0.5 * (1.0 + self.perlin.fbm(&q, octaves)),
+            NoisePattern::Turbulence { octaves } => self.perlin.turbulence(&q, octaves),
+            NoisePattern::Marble { octaves } => {
+                0.5 * (1.0 + (q.z() + 10.0 * self.perlin.turbulence(&q, octaves)).sin())
+            }
+            NoisePattern::Wood { octaves } => {
+                let radius = q.x().hypot(q.z());
+                (radius + 0.5 * self.perlin.fbm(&q, octaves)).rem_euclid(1.0)
+            }
+            NoisePattern::WorleyF1 => self.worley.distances(&q).0,
+            NoisePattern::WorleyF2 => self.worley.distances(&q).1,
+            NoisePattern::WorleyEdges => {
+                let (f1, f2) = self.worley.distances(&q);
+                f2 - f1
+            }
+        };
+
+        value.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let t = self.pattern_value(p);
+        (1.0 - t) * self.colors[0] + t * self.colors[1]
+    }
+}
+
+// Image mapped onto (u, v), with u running left to right and v bottom to top. Pixels are
+// converted from the gamma encoding of the file to linear color, inverting the gamma the image
+// writer applies, and multiplied by a scale that defaults to white.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    scale: Color,
+}
+
+impl ImageTexture {
+    // Load a PNG or a binary or plain PPM or PGM image
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|source| TextureError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        ImageTexture::decode(&data).map_err(|message| TextureError::Format {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    // Decode an image held in memory, telling the format from its first bytes
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        ImageTexture::decode_with(data, |color| color * color)
+    }
+
+    // Decode an image whose pixels hold linear data rather than gamma-encoded color, such as
+    // roughness or other material parameters
+    pub fn decode_linear(data: &[u8]) -> Result<Self, String> {
+        ImageTexture::decode_with(data, |color| color)
+    }
+
+    fn decode_with(data: &[u8], to_linear: fn(Color) -> Color) -> Result<Self, String> {
+        let image = if data.starts_with(b"\x89PNG") {
+            decode_png(data)?
+        } else if data.starts_with(b"P") {
+            parse_netpbm(data)?
+        } else {
+            return Err("unsupported image format, expected PNG, PPM or PGM".to_string());
+        };
+
+        let pixels = image
+            .values
+            .chunks_exact(image.channels)
+            .map(|c| {
+                let color = match c {
+                    [grey] | [grey, _] => Color::new(*grey, *grey, *grey),
+                    [r, g, b, ..] => Color::new(*r, *g, *b),
+                    _ => unreachable!(),
+                };
+                to_linear(color)
+            })
+            .collect();
+
+        Ok(ImageTexture {
+            width: image.width,
+            height: image.height,
+            pixels,
+            scale: Color::new(1.0, 1.0, 1.0),
+        })
+    }
+
+    pub fn with_scale(mut self, scale: Color) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // Repeat the image outside [0, 1], as tiled OBJ coordinates and glTF's default sampler
+        // expect. Flip v to image rows, which run from the top down, and pick the nearest pixel.
+        let u = u.rem_euclid(1.0);
+        let v = 1.0 - v.rem_euclid(1.0);
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+
+        self.scale * self.pixels[j * self.width + i]
+    }
+}
+
+// Decoded image with channel values scaled to [0, 1], stored row by row from the top
+pub(crate) struct DecodedImage {
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+    pub values: Vec<f64>,
+}
+
+fn decode_png(data: &[u8]) -> Result<DecodedImage, String> {
+    let mut decoder = png::Decoder::new(data);
+    // Expand palettes and low bit depths to 8 bits per channel, keeping 16-bit images as is
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+
+    let channels = info.color_type.samples();
+    let values: Vec<f64> = match info.bit_depth {
+        png::BitDepth::Sixteen => buffer[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / 65535.0)
+            .collect(),
+        _ => buffer[..info.buffer_size()]
+            .iter()
+            .map(|&b| b as f64 / 255.0)
+            .collect(),
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    if width == 0 || height == 0 || values.len() < width * height * channels {
+        return Err("image has no pixel data".to_string());
+    }
+
+    Ok(DecodedImage {
+        width,
+        height,
+        channels,
+        values,
+    })
+}
+
+// Parse a PGM (P2 or P5) or PPM (P3 or P6) image, in plain text or binary form
+pub(crate) fn parse_netpbm(data: &[u8]) -> Result<DecodedImage, String> {
+    // The header is four whitespace separated fields, with comments running from '#' to the
+    // end of the line
+    let mut fields = Vec::with_capacity(4);
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err("truncated header".to_string());
+        }
+        fields.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+    }
+
+    let magic = fields[0].as_str();
+    let (channels, plain) = match magic {
+        "P2" => (1, true),
+        "P3" => (3, true),
+        "P5" => (1, false),
+        "P6" => (3, false),
+        _ => {
+            return Err(format!(
+                "unsupported image type '{}', expected P2, P3, P5 or P6",
+                magic
+            ))
+        }
+    };
+    let parse = |field: &str, name: &str| {
+        field
+            .parse::<usize>()
+            .map_err(|_| format!("invalid {} '{}'", name, field))
+    };
+    let width = parse(&fields[1], "width")?;
+    let height = parse(&fields[2], "height")?;
+    let max_value = parse(&fields[3], "maximum value")?;
+    if width == 0 || height == 0 {
+        return Err(format!("image of {}x{} pixels is empty", width, height));
+    }
+    if max_value == 0 || max_value > 65535 {
+        return Err(format!("invalid maximum value {}", max_value));
+    }
+
+    // The size comes straight from the header, so guard against it overflowing
+    let too_large = || format!("image of {}x{} pixels is too large", width, height);
+    let count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(too_large)?;
+    let values: Vec<usize> = if plain {
+        let text = String::from_utf8_lossy(&data[pos..]);
+        let values = text
+            .split_ascii_whitespace()
+            .take(count)
+            .map(|field| parse(field, "pixel value"))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.len() < count {
+            return Err(format!("expected {} values, found {}", count, values.len()));
+        }
+        values
+    } else {
+        // A single whitespace character separates the header from the pixels, which are one
+        // byte per channel, or two bytes big endian for maximum values above 255
+        let body = &data[(pos + 1).min(data.len())..];
+        let bytes_per_value = if max_value < 256 { 1 } else { 2 };
+        let byte_count = count.checked_mul(bytes_per_value).ok_or_else(too_large)?;
+        if body.len() < byte_count {
+            return Err(format!(
+                "expected {} bytes of pixel data, found {}",
+                byte_count,
+                body.len()
+            ));
+        }
+        body.chunks_exact(bytes_per_value)
+            .take(count)
+            .map(|b| {
+                b.iter()
+                    .fold(0, |value, &byte| (value << 8) | byte as usize)
+            })
+            .collect()
+    };
+
+    Ok(DecodedImage {
+        width,
+        height,
+        channels,
+        values: values
+            .into_iter()
+            .map(|value| value as f64 / max_value as f64)
+            .collect(),
+    })
+}
+
+// Average of the texture's channels, for textures driving scalar parameters such as fuzz
+pub(crate) fn scalar_value(texture: &dyn Texture, rec: &HitRecord) -> f64 {
+    let value: Vec3 = texture.value_at(rec);
+    (value.x() + value.y() + value.z()) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(actual: Color, expected: Color) {
+        assert!(
+            (actual - expected).length() < 1e-12,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn checker_alternates_between_cells() {
+        let (white, black) = (Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        let checker = CheckerTexture::from_colors(2.0, white, black);
+        let at = |x, y, z| checker.value(0.0, 0.0, &Point3::new(x, y, z));
+
+        assert_color(at(0.5, 0.5, 0.5), white);
+        assert_color(at(1.9, 1.9, 1.9), white);
+        assert_color(at(2.5, 0.5, 0.5), black);
+        assert_color(at(0.5, 2.5, 2.5), white);
+
+        // Cells below zero continue the pattern instead of mirroring it
+        assert_color(at(-0.5, 0.5, 0.5), black);
+        assert_color(at(-0.5, -0.5, 0.5), white);
+    }
+
+    #[test]
+    fn decodes_netpbm_images_with_v_up() {
+        // Red and green over blue and white
+        let plain = b"P3\n# colors\n2 2\n255\n255 0 0  0 255 0\n0 0 255  255 255 255\n";
+        let mut binary = b"P6 2 2 255\n".to_vec();
+        binary.extend([255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+
+        for data in [&plain[..], &binary[..]] {
+            let image = ImageTexture::decode(data).unwrap();
+            assert_eq!((image.width(), image.height()), (2, 2));
+
+            let p = Point3::new(0.0, 0.0, 0.0);
+            assert_color(image.value(0.25, 0.75, &p), Color::new(1.0, 0.0, 0.0));
+            assert_color(image.value(0.75, 0.75, &p), Color::new(0.0, 1.0, 0.0));
+            assert_color(image.value(0.25, 0.25, &p), Color::new(0.0, 0.0, 1.0));
+            assert_color(image.value(0.75, 0.25, &p), Color::new(1.0, 1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn decodes_greyscale_and_converts_gamma() {
+        // 16-bit binary PGM, one row of two pixels
+        let mut data = b"P5\n2 1\n65535\n".to_vec();
+        data.extend([0x80, 0x00, 0xff, 0xff]);
+        let p = Point3::new(0.0, 0.0, 0.0);
+        let half = 32768.0 / 65535.0;
+
+        let linear = ImageTexture::decode_linear(&data).unwrap();
+        assert_color(linear.value(0.25, 0.5, &p), Color::new(half, half, half));
+
+        let image = ImageTexture::decode(b"P2 2 1 4 2 4").unwrap();
+        assert_color(image.value(0.25, 0.5, &p), Color::new(0.25, 0.25, 0.25));
+        assert_color(image.value(0.75, 0.5, &p), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn image_repeats_outside_the_unit_square() {
+        let image = ImageTexture::decode(b"P2 2 2 3 0 1 2 3").unwrap();
+        let p = Point3::new(0.0, 0.0, 0.0);
+
+        for (u, v) in [(0.25, 0.75), (0.75, 0.25), (0.6, 0.9)] {
+            let expected = image.value(u, v, &p);
+            assert_color(image.value(u + 1.0, v, &p), expected);
+            assert_color(image.value(u - 2.0, v + 3.0, &p), expected);
+            assert_color(image.value(u, v - 1.0, &p), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_netpbm_headers() {
+        let error = |data: &[u8]| match ImageTexture::decode(data) {
+            Ok(_) => panic!("decoded {:?}", String::from_utf8_lossy(data)),
+            Err(message) => message,
+        };
+
+        assert!(error(b"P6\n4294967296 4294967296\n255\n").contains("too large"));
+        assert!(error(b"P5\n4294967296 4294967295\n65535\n").contains("too large"));
+        assert!(error(b"P3 0 2 255\n").contains("empty"));
+        assert!(error(b"P2 2 2 0\n0 0 0 0").contains("maximum value"));
+        assert!(error(b"P2 2 2\n").contains("truncated header"));
+        assert!(error(b"P4 2 2 1\n").contains("unsupported image type"));
+        assert!(error(b"P2 2 2 255\n1 2 3").contains("expected 4 values"));
+        assert!(error(b"P6 2 2 255\n\x00\x00").contains("expected 12 bytes"));
+        assert!(error(b"GIF89a").contains("unsupported image format"));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    math::solve_quartic,
+    onb::Onb,
+    ray::Ray,
+    utils::PI,
+    vec3::{Point3, Vec3},
+};
+
+// Torus around the given axis: a tube of minor_radius swept around a ring of major_radius. u
+// runs once around the axis and v once around the tube, starting on the outer equator.
+pub struct Torus {
+    center: Point3,
+    onb: Onb,
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat: Material,
+    ) -> Self {
+        let major_radius = major_radius.max(0.0);
+        let minor_radius = minor_radius.max(0.0);
+
+        // The ring extends major_radius * sqrt(1 - w_i^2) along each axis i, and the tube adds
+        // minor_radius in every direction
+        let w = axis.unit_vector();
+        let extent = |w_i: f64| major_radius * (1.0 - w_i * w_i).max(0.0).sqrt() + minor_radius;
+        let e = Vec3::new(extent(w.x()), extent(w.y()), extent(w.z()));
+
+        Torus {
+            center,
+            onb: Onb::new(&axis),
+            major_radius,
+            minor_radius,
+            mat,
+            bbox: Aabb::from_points(center - e, center + e),
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let d = self.onb.to_local(&r.direction());
+        let length = d.length();
+        let d = d / length;
+
+        // Solve from the point on the ray closest to the center, so the quartic's coefficients
+        // stay small even for distant ray origins
+        let o = self.onb.to_local(&(r.origin() - self.center));
+        let t_offset = -o.dot(&d);
+        let o = o + t_offset * d;
+
+        // Substituting the ray into (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) gives a quartic
+        // in the distance s along the unit direction
+        let r2 = self.major_radius * self.major_radius;
+        let four_r2 = 4.0 * r2;
+        let f = o.dot(&d);
+        let k = o.length_squared() + r2 - self.minor_radius * self.minor_radius;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * k - four_r2 * (1.0 - d.z() * d.z()),
+            4.0 * f * k - four_r2 * (2.0 * f - 2.0 * o.z() * d.z()),
+            k * k - four_r2 * (o.length_squared() - o.z() * o.z()),
+        );
+
+        let t = roots
+            .into_iter()
+            .map(|s| (s + t_offset) / length)
+            .find(|&t| ray_t.surrounds(t))?;
+
+        // The outward normal points from the nearest point on the ring to the hit point
+        let p = self.onb.to_local(&(r.at(t) - self.center));
+        let phi = p.y().atan2(p.x());
+        let ring_point = Vec3::new(
+            self.major_radius * phi.cos(),
+            self.major_radius * phi.sin(),
+            0.0,
+        );
+        let local_normal = (p - ring_point).unit_vector();
+        let theta = local_normal
+            .z()
+            .atan2(local_normal.x() * phi.cos() + local_normal.y() * phi.sin());
+
+        let outward_normal = self.onb.transform(&local_normal);
+        let mut rec = HitRecord::new(r.at(t), t, r, &outward_normal, &self.mat);
+        rec.u = phi.rem_euclid(2.0 * PI) / (2.0 * PI);
+        rec.v = theta.rem_euclid(2.0 * PI) / (2.0 * PI);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    const MAJOR: f64 = 2.0;
+    const MINOR: f64 = 0.5;
+
+    fn material() -> Material {
+        Material::lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    // Tilted, off-center torus so the tests also cover the local frame
+    fn torus() -> Torus {
+        Torus::new(
+            Point3::new(1.0, -2.0, 3.0),
+            Vec3::new(1.0, 2.0, -0.5),
+            MAJOR,
+            MINOR,
+            material(),
+        )
+    }
+
+    // Surface point and outward normal at the given angles around the axis and the tube
+    fn surface_point(torus: &Torus, phi: f64, theta: f64) -> (Point3, Vec3) {
+        let radial = Vec3::new(phi.cos(), phi.sin(), 0.0);
+        let normal = theta.cos() * radial + Vec3::new(0.0, 0.0, theta.sin());
+        let p = MAJOR * radial + MINOR * normal;
+        (
+            torus.center + torus.onb.transform(&p),
+            torus.onb.transform(&normal),
+        )
+    }
+
+    fn samples() -> impl Iterator<Item = (f64, f64)> {
+        (0..24).flat_map(|i| {
+            (0..16).map(move |j| {
+                (
+                    2.0 * PI * (i as f64 + 0.25) / 24.0,
+                    2.0 * PI * (j as f64 + 0.5) / 16.0,
+                )
+            })
+        })
+    }
+
+    #[test]
+    fn hits_sampled_surface_points() {
+        let torus = torus();
+
+        // The nearest surface point to a point just outside the tube is the sampled point
+        // itself, so a ray shot back along the normal must hit it
+        for (phi, theta) in samples() {
+            let (p, normal) = surface_point(&torus, phi, theta);
+            let offset = 0.25;
+            let r = Ray::new(p + offset * normal, -normal);
+
+            let rec = torus
+                .hit(&r, Interval::new(0.0, f64::INFINITY))
+                .expect("ray toward surface point missed");
+            assert!((rec.t - offset).abs() < 1e-9, "t = {}", rec.t);
+            assert!((rec.p - p).length() < 1e-9);
+            assert!((rec.normal - normal).length() < 1e-9);
+            assert!(rec.front_face);
+            assert!((rec.u - phi / (2.0 * PI)).abs() < 1e-9);
+            assert!((rec.v - theta / (2.0 * PI)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn hits_from_inside_and_far_away() {
+        let torus = torus();
+
+        for (phi, theta) in samples() {
+            // From inside the tube, the ray exits through the sampled point
+            let (p, normal) = surface_point(&torus, phi, theta);
+            let r = Ray::new(p - 0.25 * normal, normal * 3.0);
+            let rec = torus.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+            assert!((rec.p - p).length() < 1e-9);
+            assert!(!rec.front_face);
+            assert!((rec.normal + normal).length() < 1e-9);
+
+            // Points on the outer equator are visible from far away along the normal
+            let (p, normal) = surface_point(&torus, phi, 0.0);
+            let r = Ray::new(p + 1e4 * normal, -normal);
+            let rec = torus.hit(&r, Interval::new(0.0, f64::INFINITY)).unwrap();
+            assert!(
+                (rec.p - p).length() < 1e-7,
+                "error {}",
+                (rec.p - p).length()
+            );
+        }
+    }
+
+    #[test]
+    fn misses_through_the_hole() {
+        let torus = torus();
+        let axis = torus.onb.w();
+        let r = Ray::new(torus.center - 10.0 * axis, axis);
+
+        assert!(torus.hit(&r, Interval::new(0.0, f64::INFINITY)).is_none());
+    }
+
+    #[test]
+    fn surface_points_lie_in_bounding_box() {
+        let torus = torus();
+        let bbox = torus.bounding_box();
+
+        for (phi, theta) in samples() {
+            let (p, _) = surface_point(&torus, phi, theta);
+            for axis in 0..3 {
+                assert!(bbox.axis_interval(axis).contains(p[axis]));
+            }
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use std::ops::Mul;
+
+use crate::{
+    aabb::Aabb,
+    ray::Ray,
+    utils,
+    vec3::{Point3, Vec3},
+};
+
+// Row-major 4x4 matrix. Points are treated as column vectors, so m * p transforms p.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Mat4 { m }
+    }
+
+    // Build a matrix from 16 values in column-major order, as used by glTF and OpenGL
+    pub fn from_column_major(values: &[f64; 16]) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (col, column) in values.chunks(4).enumerate() {
+            for (row, value) in column.iter().enumerate() {
+                m[row][col] = *value;
+            }
+        }
+        Mat4 { m }
+    }
+
+    pub fn translation(offset: &Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        m.m[0][3] = offset.x();
+        m.m[1][3] = offset.y();
+        m.m[2][3] = offset.z();
+        m
+    }
+
+    pub fn scaling(factors: &Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        m.m[0][0] = factors.x();
+        m.m[1][1] = factors.y();
+        m.m[2][2] = factors.z();
+        m
+    }
+
+    // Rotation by the given angle in degrees, counterclockwise around the axis
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin_theta, cos_theta) = utils::degrees_to_radians(degrees).sin_cos();
+        let t = 1.0 - cos_theta;
+
+        Mat4::new([
+            [
+                t * a.x() * a.x() + cos_theta,
+                t * a.x() * a.y() - sin_theta * a.z(),
+                t * a.x() * a.z() + sin_theta * a.y(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.y() + sin_theta * a.z(),
+                t * a.y() * a.y() + cos_theta,
+                t * a.y() * a.z() - sin_theta * a.x(),
+                0.0,
+            ],
+            [
+                t * a.x() * a.z() - sin_theta * a.y(),
+                t * a.y() * a.z() + sin_theta * a.x(),
+                t * a.z() * a.z() + cos_theta,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotation given by a unit quaternion stored as (x, y, z, w)
+    pub fn rotation_quaternion(q: [f64; 4]) -> Self {
+        let [x, y, z, w] = q;
+
+        Mat4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4 { m }
+    }
+
+    // Inverse by Gauss-Jordan elimination with partial pivoting, or None if the matrix is
+    // singular
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+
+    // Determinant of the upper 3x3 (linear) part
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p[0] + m[0][1] * p[1] + m[0][2] * p[2] + m[0][3],
+            m[1][0] * p[0] + m[1][1] * p[1] + m[1][2] * p[2] + m[1][3],
+            m[2][0] * p[0] + m[2][1] * p[1] + m[2][2] * p[2] + m[2][3],
+        )
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+            m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+            m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    // mat4 * mat4
+    type Output = Mat4;
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// Affine transform stored together with its inverse, mapping object space to world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    m: Mat4,
+    m_inv: Mat4,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        m: Mat4::IDENTITY,
+        m_inv: Mat4::IDENTITY,
+    };
+
+    // Returns None if the matrix is not invertible
+    pub fn new(m: Mat4) -> Option<Self> {
+        Some(Transform {
+            m,
+            m_inv: m.inverse()?,
+        })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Transform {
+            m: Mat4::translation(&offset),
+            m_inv: Mat4::translation(&-offset),
+        }
+    }
+
+    // Non-uniform scale; all factors must be nonzero
+    pub fn scale(x: f64, y: f64, z: f64) -> Self {
+        Transform {
+            m: Mat4::scaling(&Vec3::new(x, y, z)),
+            m_inv: Mat4::scaling(&Vec3::new(1.0 / x, 1.0 / y, 1.0 / z)),
+        }
+    }
+
+    // Rotation by the given angle in degrees, counterclockwise around the axis
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let m = Mat4::rotation(&axis, degrees);
+        Transform {
+            m,
+            m_inv: m.transpose(),
+        }
+    }
+
+    pub fn rotate_x(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotate_y(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotate_z(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    // Compose so that self is applied first, followed by next
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            m: next.m * self.m,
+            m_inv: self.m_inv * next.m_inv,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.m
+    }
+
+    pub fn inverse_matrix(&self) -> &Mat4 {
+        &self.m_inv
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.m.transform_point(p)
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.m.transform_vector(v)
+    }
+
+    // Normals transform by the inverse transpose so they stay perpendicular to the surface.
+    // The result is not normalized.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.m_inv.transpose().transform_vector(n)
+    }
+
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::with_time(
+            self.point(&r.origin()),
+            self.vector(&r.direction()),
+            r.time(),
+        )
+    }
+
+    // Whether the transform mirrors space, which flips the winding of transformed triangles
+    pub fn swaps_handedness(&self) -> bool {
+        self.m.determinant3() < 0.0
+    }
+
+    // Bounding box of the transformed corners of the given box
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let mut result = Aabb::EMPTY;
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { bbox.x.min } else { bbox.x.max },
+                if i & 2 == 0 { bbox.y.min } else { bbox.y.max },
+                if i & 4 == 0 { bbox.z.min } else { bbox.z.max },
+            );
+            let p = self.point(&corner);
+            result = Aabb::surrounding(&result, &Aabb::from_points(p, p));
+        }
+        result
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+// Transform split into translation, rotation and scale at a point in time, for animating
+// instances. Translation and scale are interpolated linearly between keyframes and rotation
+// spherically, which keeps in-between transforms rigid.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: [f64; 4], // unit quaternion (x, y, z, w)
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    // Keyframe with a rotation by the given angle in degrees, counterclockwise around the axis
+    pub fn new(time: f64, translation: Vec3, axis: Vec3, degrees: f64, scale: Vec3) -> Self {
+        let a = axis.unit_vector();
+        let (sin_half, cos_half) = (utils::degrees_to_radians(degrees) / 2.0).sin_cos();
+
+        Keyframe {
+            time,
+            translation,
+            rotation: [
+                a.x() * sin_half,
+                a.y() * sin_half,
+                a.z() * sin_half,
+                cos_half,
+            ],
+            scale,
+        }
+    }
+
+    // Applied as scale, then rotation, then translation
+    pub fn transform(&self) -> Transform {
+        let s = self.scale;
+        let rotation = Mat4::rotation_quaternion(self.rotation);
+
+        Transform {
+            m: Mat4::translation(&self.translation) * rotation * Mat4::scaling(&s),
+            m_inv: Mat4::scaling(&Vec3::new(1.0 / s.x(), 1.0 / s.y(), 1.0 / s.z()))
+                * rotation.transpose()
+                * Mat4::translation(&-self.translation),
+        }
+    }
+
+    // Interpolate between keyframes a and b at the given time
+    pub fn interpolate(a: &Keyframe, b: &Keyframe, time: f64) -> Keyframe {
+        let span = b.time - a.time;
+        let t = if span > 0.0 {
+            ((time - a.time) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Keyframe {
+            time,
+            translation: (1.0 - t) * a.translation + t * b.translation,
+            rotation: slerp(a.rotation, b.rotation, t),
+            scale: (1.0 - t) * a.scale + t * b.scale,
+        }
+    }
+}
+
+// Spherical linear interpolation between unit quaternions, along the shorter arc
+fn slerp(q0: [f64; 4], q1: [f64; 4], t: f64) -> [f64; 4] {
+    let mut cos_theta: f64 = (0..4).map(|i| q0[i] * q1[i]).sum();
+    let mut q1 = q1;
+    if cos_theta < 0.0 {
+        cos_theta = -cos_theta;
+        q1 = q1.map(|c| -c);
+    }
+
+    let (w0, w1) = if cos_theta > 0.9995 {
+        // Nearly parallel, linear interpolation is accurate and avoids dividing by sin(theta)
+        (1.0 - t, t)
+    } else {
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        (
+            ((1.0 - t) * theta).sin() / sin_theta,
+            (t * theta).sin() / sin_theta,
+        )
+    };
+
+    let q = [0, 1, 2, 3].map(|i| w0 * q0[i] + w1 * q1[i]);
+    let len = q.iter().map(|c| c * c).sum::<f64>().sqrt();
+    q.map(|c| c / len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(m: &Mat4) {
+        for (i, row) in m.m.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!(
+                    (value - expected).abs() < 1e-12,
+                    "m[{}][{}] = {}",
+                    i,
+                    j,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Mat4::new([
+            [2.0, 0.5, 0.0, 1.0],
+            [0.3, 1.0, -0.2, 2.0],
+            [0.0, 0.4, 3.0, -1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let m_inv = m.inverse().unwrap();
+        assert_identity(&(m * m_inv));
+        assert_identity(&(m_inv * m));
+
+        let t = Transform::scale(2.0, -0.5, 3.0)
+            .then(&Transform::rotate(Vec3::new(1.0, 2.0, -1.0), 37.0))
+            .then(&Transform::translate(Vec3::new(4.0, -3.0, 1.0)));
+        assert_identity(&(*t.matrix() * *t.inverse_matrix()));
+        assert!(t.swaps_handedness());
+
+        let singular = Mat4::scaling(&Vec3::new(1.0, 0.0, 1.0));
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let t = Transform::scale(1.0, 4.0, 0.5).then(&Transform::rotate_z(30.0));
+
+        // Two tangents of a slanted surface and its normal
+        let a = Vec3::new(1.0, 1.0, 0.0);
+        let b = Vec3::new(0.0, 1.0, 1.0);
+        let n = a.cross(&b);
+
+        let world_n = t.normal(&n);
+        assert!(world_n.dot(&t.vector(&a)).abs() < 1e-12);
+        assert!(world_n.dot(&t.vector(&b)).abs() < 1e-12);
+
+        // Transforming the normal like a direction would tilt it off the surface
+        assert!(t.vector(&n).dot(&t.vector(&a)).abs() > 0.1);
+    }
+
+    fn assert_quaternion(q: [f64; 4], expected: [f64; 4]) {
+        // q and -q are the same rotation
+        let dot: f64 = (0..4).map(|i| q[i] * expected[i]).sum();
+        assert!((dot.abs() - 1.0).abs() < 1e-12, "{:?} != {:?}", q, expected);
+    }
+
+    #[test]
+    fn slerp_hits_endpoints_and_midpoint() {
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        let rotation =
+            |degrees| Keyframe::new(0.0, Vec3::default(), axis, degrees, Vec3::default()).rotation;
+
+        for (start, end) in [(10.0, 100.0), (0.0, 170.0), (20.0, 20.5)] {
+            let (q0, q1) = (rotation(start), rotation(end));
+            assert_quaternion(slerp(q0, q1, 0.0), q0);
+            assert_quaternion(slerp(q0, q1, 1.0), q1);
+            assert_quaternion(slerp(q0, q1, 0.5), rotation(0.5 * (start + end)));
+            assert_quaternion(slerp(q0, q1, 0.25), rotation(0.75 * start + 0.25 * end));
+        }
+
+        // Across the double cover, the shorter arc from 170 to 190 degrees passes through 180
+        let (q0, q1) = (rotation(170.0), rotation(190.0));
+        assert_quaternion(slerp(q0, q1, 0.5), rotation(180.0));
+        assert_quaternion(slerp(q0, q1.map(|c| -c), 0.5), rotation(180.0));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+// Watertight ray/triangle intersection (Woop, Benthin and Wald 2013). Rays that pass exactly
+// through a shared edge or vertex hit at least one of the adjacent triangles, so meshes have no
+// cracks. Returns the ray parameter t and the barycentric weights of p0, p1 and p2.
+pub fn intersect_triangle(
+    r: &Ray,
+    ray_t: Interval,
+    p0: Point3,
+    p1: Point3,
+    p2: Point3,
+) -> Option<(f64, [f64; 3])> {
+    let dir = r.direction();
+
+    // Translate vertices to the ray origin and permute axes so the ray direction's largest
+    // component is along z
+    let kz = max_dimension(&dir);
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    let d = permute(&dir, kx, ky, kz);
+    let mut p0t = permute(&(p0 - r.origin()), kx, ky, kz);
+    let mut p1t = permute(&(p1 - r.origin()), kx, ky, kz);
+    let mut p2t = permute(&(p2 - r.origin()), kx, ky, kz);
+
+    // Shear so the ray direction becomes +z
+    let sx = -d.x() / d.z();
+    let sy = -d.y() / d.z();
+    let sz = 1.0 / d.z();
+    for p in [&mut p0t, &mut p1t, &mut p2t] {
+        p[0] += sx * p[2];
+        p[1] += sy * p[2];
+    }
+
+    // Edge functions; the ray hits when they all have the same sign
+    let e0 = p1t.x() * p2t.y() - p1t.y() * p2t.x();
+    let e1 = p2t.x() * p0t.y() - p2t.y() * p0t.x();
+    let e2 = p0t.x() * p1t.y() - p0t.y() * p1t.x();
+
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t_scaled = sz * (e0 * p0t.z() + e1 * p1t.z() + e2 * p2t.z());
+    let t = t_scaled / det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    Some((t, [e0 * inv_det, e1 * inv_det, e2 * inv_det]))
+}
+
+fn max_dimension(v: &Vec3) -> usize {
+    let (x, y, z) = (v.x().abs(), v.y().abs(), v.z().abs());
+    if x > y {
+        if x > z {
+            0
+        } else {
+            2
+        }
+    } else if y > z {
+        1
+    } else {
+        2
+    }
+}
+
+fn permute(v: &Vec3, x: usize, y: usize, z: usize) -> Vec3 {
+    Vec3::new(v[x], v[y], v[z])
+}
+
+// Single flat-shaded triangle. Use TriangleMesh for anything with more than a handful of faces.
+// Degenerate triangles, with collinear points, have no normal and are never hit.
+pub struct Triangle {
+    p0: Point3,
+    p1: Point3,
+    p2: Point3,
+    normal: Option<Vec3>,
+    mat: Material,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, mat: Material) -> Self {
+        let bbox = Aabb::surrounding(&Aabb::from_points(p0, p1), &Aabb::from_points(p2, p2));
+        let n = (p1 - p0).cross(&(p2 - p0));
+
+        Triangle {
+            p0,
+            p1,
+            p2,
+            normal: (n.length_squared() > 0.0).then(|| n.unit_vector()),
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let normal = self.normal?;
+        let (t, b) = intersect_triangle(r, ray_t, self.p0, self.p1, self.p2)?;
+
+        let mut rec = HitRecord::new(r.at(t), t, r, &normal, &self.mat);
+        rec.u = b[1];
+        rec.v = b[2];
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
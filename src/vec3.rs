@@ -5,6 +5,8 @@ use std::ops::{
 
 use rand::Rng;
 
+use crate::utils::PI;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Vec3 {
     pub e: [f64; 3],
@@ -104,6 +106,20 @@ impl Vec3 {
             -on_unit_sphere
         }
     }
+
+    // Random unit vector around +z, with density cos(theta) / pi over the upper hemisphere
+    pub fn random_cosine_direction() -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let r1 = rng.gen::<f64>();
+        let r2 = rng.gen::<f64>();
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+
+        Vec3::new(x, y, z)
+    }
 }
 
 impl Default for Vec3 {
@@ -0,0 +1,660 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use rand::Rng;
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+
+// Voxels along each side of a brick of a sparse grid
+const BRICK_SIZE: usize = 8;
+
+// Voxels along each side of a cell of the majorant grid
+const MAJORANT_BLOCK: usize = 8;
+
+// Marks a brick with no nonzero voxels, which is not stored
+const EMPTY_BRICK: u32 = u32::MAX;
+
+#[derive(Debug)]
+pub enum VolumeError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VolumeError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            VolumeError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for VolumeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VolumeError::Io { source, .. } => Some(source),
+            VolumeError::Format { .. } => None,
+        }
+    }
+}
+
+// 3D grid of voxels, each with a density and a scattering albedo. Voxels are indexed by
+// [x, y, z], with x varying fastest in files and storage.
+pub trait VoxelGrid {
+    fn resolution(&self) -> [usize; 3];
+    fn density(&self, index: [usize; 3]) -> f64;
+    fn albedo(&self, index: [usize; 3]) -> Color;
+}
+
+// Grid storing every voxel. Voxels without an albedo of their own scatter all light.
+#[derive(Debug, Clone)]
+pub struct DenseGrid {
+    resolution: [usize; 3],
+    densities: Vec<f32>,
+    albedos: Vec<Color>,
+}
+
+impl DenseGrid {
+    pub fn new(resolution: [usize; 3], densities: Vec<f32>) -> Self {
+        assert!(
+            resolution.iter().all(|&n| n > 0),
+            "voxel grid needs at least one voxel along each axis"
+        );
+        assert_eq!(
+            densities.len(),
+            resolution.iter().product::<usize>(),
+            "density count doesn't match grid size"
+        );
+
+        DenseGrid {
+            resolution,
+            densities,
+            albedos: Vec::new(),
+        }
+    }
+
+    pub fn with_albedo(mut self, albedos: Vec<Color>) -> Self {
+        assert_eq!(
+            albedos.len(),
+            self.densities.len(),
+            "albedo count doesn't match grid size"
+        );
+        self.albedos = albedos;
+        self
+    }
+
+    // Load densities stored as raw little endian 32-bit floats. Densities must be finite and
+    // non-negative, or the majorants would no longer bound them.
+    pub fn load_raw<P: AsRef<Path>>(path: P, resolution: [usize; 3]) -> Result<Self, VolumeError> {
+        let path = path.as_ref();
+        let densities = read_floats(path, resolution, 1)?;
+        if let Some((i, density)) = densities
+            .iter()
+            .enumerate()
+            .find(|(_, density)| !(density.is_finite() && **density >= 0.0))
+        {
+            return Err(VolumeError::Format {
+                path: path.to_path_buf(),
+                message: format!("voxel {} has invalid density {}", i, density),
+            });
+        }
+        Ok(DenseGrid::new(resolution, densities))
+    }
+
+    // Load per-voxel albedos stored as raw little endian 32-bit floats, three per voxel
+    pub fn load_albedo_raw<P: AsRef<Path>>(self, path: P) -> Result<Self, VolumeError> {
+        let floats = read_floats(path.as_ref(), self.resolution, 3)?;
+        let albedos = floats
+            .chunks_exact(3)
+            .map(|c| Color::new(c[0] as f64, c[1] as f64, c[2] as f64))
+            .collect();
+        Ok(self.with_albedo(albedos))
+    }
+
+    fn offset(&self, [i, j, k]: [usize; 3]) -> usize {
+        (k * self.resolution[1] + j) * self.resolution[0] + i
+    }
+}
+
+impl VoxelGrid for DenseGrid {
+    fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    fn density(&self, index: [usize; 3]) -> f64 {
+        self.densities[self.offset(index)] as f64
+    }
+
+    fn albedo(&self, index: [usize; 3]) -> Color {
+        if self.albedos.is_empty() {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            self.albedos[self.offset(index)]
+        }
+    }
+}
+
+// Grid split into bricks of 8x8x8 voxels where only bricks holding some density are stored,
+// for volumes such as clouds that leave most of their bounds empty
+pub struct BrickGrid {
+    resolution: [usize; 3],
+    bricks: [usize; 3],
+    brick_index: Vec<u32>,
+    densities: Vec<f32>,
+    albedos: Vec<Color>,
+}
+
+impl BrickGrid {
+    pub fn from_dense(grid: &DenseGrid) -> Self {
+        let resolution = grid.resolution;
+        let bricks = resolution.map(|n| n.div_ceil(BRICK_SIZE));
+        let brick_voxels = BRICK_SIZE * BRICK_SIZE * BRICK_SIZE;
+
+        let mut sparse = BrickGrid {
+            resolution,
+            bricks,
+            brick_index: vec![EMPTY_BRICK; bricks.iter().product()],
+            densities: Vec::new(),
+            albedos: Vec::new(),
+        };
+
+        for bk in 0..bricks[2] {
+            for bj in 0..bricks[1] {
+                for bi in 0..bricks[0] {
+                    // Voxels of the brick, with those past the edge of the grid left empty
+                    let voxels: Vec<Option<[usize; 3]>> = brick_voxel_indices()
+                        .map(|[i, j, k]| {
+                            let index = [
+                                bi * BRICK_SIZE + i,
+                                bj * BRICK_SIZE + j,
+                                bk * BRICK_SIZE + k,
+                            ];
+                            (0..3).all(|a| index[a] < resolution[a]).then_some(index)
+                        })
+                        .collect();
+
+                    if voxels.iter().flatten().all(|&v| grid.density(v) == 0.0) {
+                        continue;
+                    }
+
+                    let brick = (bk * bricks[1] + bj) * bricks[0] + bi;
+                    sparse.brick_index[brick] = (sparse.densities.len() / brick_voxels) as u32;
+                    for voxel in voxels {
+                        let density = voxel.map_or(0.0, |v| grid.densities[grid.offset(v)]);
+                        sparse.densities.push(density);
+                        if !grid.albedos.is_empty() {
+                            let albedo = voxel.map_or(Color::new(1.0, 1.0, 1.0), |v| {
+                                grid.albedos[grid.offset(v)]
+                            });
+                            sparse.albedos.push(albedo);
+                        }
+                    }
+                }
+            }
+        }
+
+        sparse
+    }
+
+    pub fn load_raw<P: AsRef<Path>>(path: P, resolution: [usize; 3]) -> Result<Self, VolumeError> {
+        Ok(BrickGrid::from_dense(&DenseGrid::load_raw(
+            path, resolution,
+        )?))
+    }
+
+    // Number of bricks stored, out of the number covering the grid
+    pub fn brick_count(&self) -> (usize, usize) {
+        let stored = self.densities.len() / (BRICK_SIZE * BRICK_SIZE * BRICK_SIZE);
+        (stored, self.brick_index.len())
+    }
+
+    // Position of the voxel in the brick storage, or None if its brick is empty
+    fn offset(&self, index: [usize; 3]) -> Option<usize> {
+        let [bi, bj, bk] = index.map(|n| n / BRICK_SIZE);
+        let brick = self.brick_index[(bk * self.bricks[1] + bj) * self.bricks[0] + bi];
+        if brick == EMPTY_BRICK {
+            return None;
+        }
+
+        let [i, j, k] = index.map(|n| n % BRICK_SIZE);
+        let within = (k * BRICK_SIZE + j) * BRICK_SIZE + i;
+        Some(brick as usize * BRICK_SIZE * BRICK_SIZE * BRICK_SIZE + within)
+    }
+}
+
+impl VoxelGrid for BrickGrid {
+    fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    fn density(&self, index: [usize; 3]) -> f64 {
+        self.offset(index)
+            .map_or(0.0, |offset| self.densities[offset] as f64)
+    }
+
+    fn albedo(&self, index: [usize; 3]) -> Color {
+        match self.offset(index) {
+            Some(offset) if !self.albedos.is_empty() => self.albedos[offset],
+            _ => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// Heterogeneous participating medium, such as a cloud or an explosion, with density and albedo
+// interpolated trilinearly from a voxel grid filling the box from min to max. Scattering
+// distances are sampled by delta tracking, and transmittance along shadow rays can be estimated
+// by ratio tracking. Both walk a coarse grid of density upper bounds (majorants), so empty and
+// thin regions are crossed in a few large steps.
+pub struct GridMedium {
+    grid: Box<dyn VoxelGrid>,
+    bbox: Aabb,
+    voxel_size: Vec3,
+    density_scale: f64,
+    albedo: Color,
+    majorant_resolution: [usize; 3],
+    majorants: Vec<f64>,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: Box<dyn VoxelGrid>,
+        min: Point3,
+        max: Point3,
+        density_scale: f64,
+        albedo: Color,
+    ) -> Self {
+        let resolution = grid.resolution();
+        let bbox = Aabb::from_points(min, max);
+        let voxel_size = Vec3::new(
+            bbox.x.size() / resolution[0] as f64,
+            bbox.y.size() / resolution[1] as f64,
+            bbox.z.size() / resolution[2] as f64,
+        );
+        let majorant_resolution = resolution.map(|n| n.div_ceil(MAJORANT_BLOCK));
+
+        // Interpolated density anywhere in a cell is bounded by the voxels of its block plus the
+        // neighboring layer of voxels that share the interpolation
+        let mut majorants = Vec::with_capacity(majorant_resolution.iter().product());
+        for ck in 0..majorant_resolution[2] {
+            for cj in 0..majorant_resolution[1] {
+                for ci in 0..majorant_resolution[0] {
+                    let range = |c: usize, n: usize| {
+                        (c * MAJORANT_BLOCK).saturating_sub(1)
+                            ..((c + 1) * MAJORANT_BLOCK + 1).min(n)
+                    };
+                    let mut max_density = 0.0f64;
+                    for k in range(ck, resolution[2]) {
+                        for j in range(cj, resolution[1]) {
+                            for i in range(ci, resolution[0]) {
+                                max_density = max_density.max(grid.density([i, j, k]));
+                            }
+                        }
+                    }
+                    majorants.push(max_density * density_scale);
+                }
+            }
+        }
+
+        GridMedium {
+            grid,
+            bbox,
+            voxel_size,
+            density_scale,
+            albedo,
+            majorant_resolution,
+            majorants,
+        }
+    }
+
+    // Density and albedo at p, interpolated between voxel centers
+    fn sample(&self, p: &Point3) -> (f64, Color) {
+        let resolution = self.grid.resolution();
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut fraction = [0.0; 3];
+        for axis in 0..3 {
+            let x = (p[axis] - self.bbox.axis_interval(axis).min) / self.voxel_size[axis] - 0.5;
+            let i = (x.floor().max(0.0) as usize).min(resolution[axis] - 1);
+            lower[axis] = i;
+            upper[axis] = (i + 1).min(resolution[axis] - 1);
+            fraction[axis] = (x - i as f64).clamp(0.0, 1.0);
+        }
+
+        let mut density = 0.0;
+        let mut albedo = Color::new(0.0, 0.0, 0.0);
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                if corner & (1 << axis) == 0 {
+                    index[axis] = lower[axis];
+                    weight *= 1.0 - fraction[axis];
+                } else {
+                    index[axis] = upper[axis];
+                    weight *= fraction[axis];
+                }
+            }
+            if weight > 0.0 {
+                density += weight * self.grid.density(index);
+                albedo += weight * self.grid.albedo(index);
+            }
+        }
+
+        (density * self.density_scale, albedo)
+    }
+
+    // Walk the majorant cells along the ray through the interval, which must lie inside the
+    // box, calling visit with each cell's entry, exit and majorant until it returns a result.
+    // Cells with a zero majorant are skipped.
+    fn walk_majorants<T, F>(&self, r: &Ray, ray_t: Interval, mut visit: F) -> Option<T>
+    where
+        F: FnMut(f64, f64, f64) -> Option<T>,
+    {
+        // Walk in majorant grid coordinates, where cells are unit cubes
+        let cells = self.majorant_resolution;
+        let cell_size = MAJORANT_BLOCK as f64 * self.voxel_size;
+        let mut origin = [0.0; 3];
+        let mut dir = [0.0; 3];
+        let mut cell = [0; 3];
+        let mut step = [0isize; 3];
+        let mut t_next = [f64::INFINITY; 3];
+        let mut t_delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            origin[axis] = (r.origin()[axis] - self.bbox.axis_interval(axis).min) / cell_size[axis];
+            dir[axis] = r.direction()[axis] / cell_size[axis];
+
+            let start = origin[axis] + ray_t.min * dir[axis];
+            cell[axis] = (start.floor().max(0.0) as usize).min(cells[axis] - 1);
+
+            if dir[axis] > 0.0 {
+                step[axis] = 1;
+                t_next[axis] = ((cell[axis] + 1) as f64 - origin[axis]) / dir[axis];
+                t_delta[axis] = 1.0 / dir[axis];
+            } else if dir[axis] < 0.0 {
+                step[axis] = -1;
+                t_next[axis] = (cell[axis] as f64 - origin[axis]) / dir[axis];
+                t_delta[axis] = -1.0 / dir[axis];
+            }
+        }
+
+        let mut t_enter = ray_t.min;
+        loop {
+            let axis = (0..3)
+                .min_by(|&a, &b| t_next[a].total_cmp(&t_next[b]))
+                .unwrap();
+            let t_exit = t_next[axis].min(ray_t.max);
+
+            let majorant = self.majorants[(cell[2] * cells[1] + cell[1]) * cells[0] + cell[0]];
+            if majorant > 0.0 && t_exit > t_enter {
+                if let Some(result) = visit(t_enter, t_exit, majorant) {
+                    return Some(result);
+                }
+            }
+
+            if t_exit >= ray_t.max {
+                return None;
+            }
+
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= cells[axis] as isize {
+                return None;
+            }
+            cell[axis] = next as usize;
+            t_enter = t_next[axis];
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let clipped = self.bbox.clip(r, ray_t)?;
+
+        // Delta tracking: sample tentative collisions against the majorant and accept each as a
+        // real scattering event with probability density / majorant
+        let ray_length = r.direction().length();
+        let mut rng = rand::thread_rng();
+        let (t, albedo) = self.walk_majorants(r, clipped, |t_enter, t_exit, majorant| {
+            let mut t = t_enter;
+            loop {
+                t -= (1.0 - rng.gen::<f64>()).ln() / (majorant * ray_length);
+                if t >= t_exit {
+                    return None;
+                }
+                let (density, albedo) = self.sample(&r.at(t));
+                if rng.gen::<f64>() * majorant < density {
+                    return Some((t, albedo));
+                }
+            }
+        })?;
+
+        Some(HitRecord {
+            p: r.at(t),
+            normal: Vec3::new(1.0, 0.0, 0.0), // arbitrary
+            mat: Cow::Owned(Material::Isotropic {
+                albedo: self.albedo * albedo,
+            }),
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face: true, // also arbitrary
+            tangent: Vec3::new(0.0, 0.0, 0.0),
+            color: Color::new(1.0, 1.0, 1.0),
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn shadow_hit(&self, _r: &Ray, _ray_t: Interval) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    // Estimate the fraction of light passing along the ray over the interval, by ratio
+    // tracking. The estimate is unbiased, and smoother than the 0 or 1 from delta tracking.
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let Some(clipped) = self.bbox.clip(r, ray_t) else {
+            return 1.0;
+        };
+
+        let ray_length = r.direction().length();
+        let mut rng = rand::thread_rng();
+        let mut transmittance = 1.0;
+        self.walk_majorants::<(), _>(r, clipped, |t_enter, t_exit, majorant| {
+            let mut t = t_enter;
+            loop {
+                t -= (1.0 - rng.gen::<f64>()).ln() / (majorant * ray_length);
+                if t >= t_exit {
+                    return None;
+                }
+                let (density, _) = self.sample(&r.at(t));
+                transmittance *= 1.0 - density / majorant;
+            }
+        });
+
+        transmittance
+    }
+}
+
+// Offsets of the voxels within a brick, in storage order
+fn brick_voxel_indices() -> impl Iterator<Item = [usize; 3]> {
+    (0..BRICK_SIZE)
+        .flat_map(|k| (0..BRICK_SIZE).flat_map(move |j| (0..BRICK_SIZE).map(move |i| [i, j, k])))
+}
+
+// Read the given number of floats per voxel of a grid of the given resolution
+fn read_floats(
+    path: &Path,
+    resolution: [usize; 3],
+    components: usize,
+) -> Result<Vec<f32>, VolumeError> {
+    let [nx, ny, nz] = resolution;
+    let error = |message: String| VolumeError::Format {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    // The resolution comes from the caller, often straight from a file name or header, so
+    // check it before trusting the size it implies
+    if resolution.contains(&0) {
+        return Err(error(format!("{}x{}x{} grid has no voxels", nx, ny, nz)));
+    }
+    let expected = resolution
+        .iter()
+        .try_fold(components * 4, |bytes, &n| bytes.checked_mul(n))
+        .ok_or_else(|| error(format!("{}x{}x{} grid is too large", nx, ny, nz)))?;
+
+    let data = fs::read(path).map_err(|source| VolumeError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    if data.len() != expected {
+        return Err(error(format!(
+            "expected {} bytes for a {}x{}x{} grid, found {}",
+            expected,
+            nx,
+            ny,
+            nz,
+            data.len()
+        )));
+    }
+
+    Ok(data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit cube filled with a constant density of sigma
+    fn constant_medium(sigma: f64) -> GridMedium {
+        let grid = DenseGrid::new([4, 4, 4], vec![0.5; 64]);
+        GridMedium::new(
+            Box::new(grid),
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            2.0 * sigma,
+            Color::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    fn mean_transmittance(medium: &GridMedium, r: &Ray, ray_t: Interval) -> f64 {
+        let samples = 20000;
+        (0..samples)
+            .map(|_| medium.transmittance(r, ray_t))
+            .sum::<f64>()
+            / samples as f64
+    }
+
+    #[test]
+    fn ratio_tracking_matches_beer_lambert() {
+        let sigma = 1.5;
+        let medium = constant_medium(sigma);
+
+        // Across the whole cube, from outside, with a direction that isn't unit length
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(2.0, 0.0, 0.0));
+        let expected = (-sigma).exp();
+        let estimate = mean_transmittance(&medium, &r, Interval::new(0.0, f64::INFINITY));
+        assert!(
+            (estimate - expected).abs() < 0.02,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+
+        // From inside the cube, along a diagonal up to a point short of the far side
+        let dir = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+        let r = Ray::new(Point3::new(0.25, 0.25, 0.5), dir);
+        let distance = 0.5;
+        let expected = (-sigma * distance).exp();
+        let estimate = mean_transmittance(&medium, &r, Interval::new(0.0, distance));
+        assert!(
+            (estimate - expected).abs() < 0.02,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+
+        // Rays that miss the box pass unattenuated
+        let r = Ray::new(Point3::new(-1.0, 2.0, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            medium.transmittance(&r, Interval::new(0.0, f64::INFINITY)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn delta_tracking_scatters_at_the_expected_rate() {
+        let sigma = 1.5;
+        let medium = constant_medium(sigma);
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+
+        let samples = 20000;
+        let hits = (0..samples)
+            .filter_map(|_| medium.hit(&r, Interval::new(0.0, f64::INFINITY)))
+            .inspect(|rec| assert!(medium.bounding_box().x.contains(rec.p.x())))
+            .count();
+        let expected = 1.0 - (-sigma).exp();
+        let rate = hits as f64 / samples as f64;
+        assert!((rate - expected).abs() < 0.02, "{} vs {}", rate, expected);
+    }
+
+    #[test]
+    fn load_raw_rejects_invalid_files() {
+        let dir =
+            std::env::temp_dir().join(format!("raytracer-volume-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, values: &[f32]| {
+            let path = dir.join(name);
+            let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            fs::write(&path, bytes).unwrap();
+            path
+        };
+
+        let valid = write("valid.raw", &[0.0, 0.5, 1.0, 2.0]);
+        let negative = write("negative.raw", &[0.0, -0.5, 1.0, 2.0]);
+        let nan = write("nan.raw", &[0.0, 0.5, f32::NAN, 2.0]);
+        let results = [
+            DenseGrid::load_raw(&valid, [2, 2, 1]).map(|_| ()),
+            DenseGrid::load_raw(&valid, [2, 2, 0]).map(|_| ()),
+            DenseGrid::load_raw(&valid, [usize::MAX, 2, 2]).map(|_| ()),
+            DenseGrid::load_raw(&valid, [2, 2, 2]).map(|_| ()),
+            DenseGrid::load_raw(&negative, [2, 2, 1]).map(|_| ()),
+            DenseGrid::load_raw(&nan, [2, 2, 1]).map(|_| ()),
+            BrickGrid::load_raw(&nan, [4, 1, 1]).map(|_| ()),
+        ];
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(results[0].is_ok());
+        let messages: Vec<String> = results[1..]
+            .iter()
+            .map(|result| match result {
+                Err(VolumeError::Format { message, .. }) => message.clone(),
+                _ => panic!("expected a format error"),
+            })
+            .collect();
+        assert!(messages[0].contains("no voxels"));
+        assert!(messages[1].contains("too large"));
+        assert!(messages[2].contains("expected 32 bytes"));
+        assert!(messages[3].contains("voxel 1 has invalid density -0.5"));
+        assert!(messages[4].contains("voxel 2 has invalid density NaN"));
+        assert!(messages[5].contains("invalid density"));
+    }
+}